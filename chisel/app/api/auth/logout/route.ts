@@ -1,15 +1,28 @@
-import { NextResponse } from 'next/server';
+import { NextRequest, NextResponse } from 'next/server';
+import config from '@/_config';
+import { clearTokenCookies } from '../tokens';
 
-export async function POST() {
-  const response = NextResponse.json({ success: true });
-
-  // Clear the auth cookie
-  response.cookies.set('auth_token', '', {
-    httpOnly: true,
-    secure: process.env.NODE_ENV === 'production',
-    sameSite: 'lax',
-    maxAge: 0, // Expire immediately
-  });
+export async function POST(request: NextRequest) {
+  const authToken = request.cookies.get('auth_token');
+  const refreshToken = request.cookies.get('refresh_token');
+  if (authToken !== undefined || refreshToken !== undefined) {
+    // Revoke the tokens so they cannot be used even if they leaked
+    try {
+      await fetch(config.SERVER_API_ENDPOINT + '/auth/logout', {
+        method: 'POST',
+        body: JSON.stringify({ refresh_token: refreshToken?.value }),
+        headers: {
+          'Content-Type': 'application/json',
+          ...(authToken && { Authorization: `Bearer ${authToken.value}` }),
+        },
+      });
+    } catch (error) {
+      console.error('Error revoking the tokens:', error);
+    }
+  }
 
+  const response = NextResponse.json({ success: true });
+  // Clear the auth cookies
+  clearTokenCookies(response);
   return response;
 }
@@ -0,0 +1,60 @@
+import { NextResponse } from "next/server";
+import config from "@/_config";
+
+export interface BackendLoginResponse {
+  jwt: string;
+  expires_at: number;
+  refresh_token: string;
+  email: string;
+  role: string;
+}
+
+// Long enough for the backend to accept the refresh token; the backend enforces the real expiry
+const REFRESH_TOKEN_MAX_AGE = 60 * 60 * 24 * 30;
+
+export function setTokenCookies(
+  response: NextResponse,
+  tokens: BackendLoginResponse,
+) {
+  const maxAge = Math.max(0, tokens.expires_at - Math.floor(Date.now() / 1000));
+  response.cookies.set("auth_token", tokens.jwt, {
+    path: "/",
+    httpOnly: true,
+    secure: true,
+    sameSite: "strict",
+    maxAge,
+  });
+  response.cookies.set("refresh_token", tokens.refresh_token, {
+    path: "/api/auth",
+    httpOnly: true,
+    secure: true,
+    sameSite: "strict",
+    maxAge: REFRESH_TOKEN_MAX_AGE,
+  });
+}
+
+export function clearTokenCookies(response: NextResponse) {
+  response.cookies.set("auth_token", "", { path: "/", maxAge: 0 });
+  response.cookies.set("refresh_token", "", { path: "/api/auth", maxAge: 0 });
+}
+
+// Exchanges the refresh token for new tokens, or returns null if it is no longer valid
+export async function refreshTokens(
+  refreshToken: string,
+): Promise<BackendLoginResponse | null> {
+  const backendResponse = await fetch(
+    config.SERVER_API_ENDPOINT + "/auth/refresh",
+    {
+      method: "POST",
+      body: JSON.stringify({ refresh_token: refreshToken }),
+      headers: {
+        Accept: "application/json",
+        "Content-Type": "application/json",
+      },
+    },
+  );
+  if (!backendResponse.ok) {
+    return null;
+  }
+  return backendResponse.json();
+}
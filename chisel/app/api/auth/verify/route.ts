@@ -1,36 +1,61 @@
 import { NextRequest, NextResponse } from "next/server";
 import config from "@/_config";
+import { refreshTokens, setTokenCookies } from "../tokens";
+
+async function verifyToken(authToken: string): Promise<Response> {
+  return fetch(
+    `${config.SERVER_API_ENDPOINT}/auth/verify?` +
+      new URLSearchParams({
+        auth_token: authToken,
+      }),
+  );
+}
 
 export async function GET(request: NextRequest) {
   try {
     const cookie = request.cookies.get("auth_token");
-    if (cookie === undefined) {
+    const refreshCookie = request.cookies.get("refresh_token");
+    if (cookie === undefined && refreshCookie === undefined) {
       console.debug("No cookie found");
       return NextResponse.json({
         status: 401,
         message: "No authorization cookie found",
       });
     }
-    const backendResponse = await fetch(
-      `${config.SERVER_API_ENDPOINT}/auth/verify?` +
-        new URLSearchParams({
-          auth_token: cookie.value,
-        }),
-    );
-    if (!backendResponse.ok) {
+    if (cookie !== undefined) {
+      const backendResponse = await verifyToken(cookie.value);
+      if (backendResponse.ok) {
+        const body = await backendResponse.json();
+        return NextResponse.json({
+          success: true,
+          body: { ...body, jwt: cookie.value },
+        });
+      }
+    }
+
+    // The access token expired or was revoked, so exchange the refresh token for new ones
+    const tokens = refreshCookie && (await refreshTokens(refreshCookie.value));
+    if (!tokens) {
       console.error("Backend return 401");
       return NextResponse.json({
         message: "Unauthorized",
         status: 401,
       });
     }
+    const backendResponse = await verifyToken(tokens.jwt);
+    if (!backendResponse.ok) {
+      return NextResponse.json({
+        message: "Unauthorized",
+        status: 401,
+      });
+    }
     const body = await backendResponse.json();
-    const responseBody = { ...body, jwt: cookie.value };
-
-    return NextResponse.json({
+    const response = NextResponse.json({
       success: true,
-      body: responseBody,
+      body: { ...body, jwt: tokens.jwt },
     });
+    setTokenCookies(response, tokens);
+    return response;
   } catch (error) {
     console.error("Error verifying user:", error);
     return NextResponse.json({ error: "Bad request" }, { status: 400 });
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+/// With the `embedded` feature, lists every file of `resources/db` in
+/// `$OUT_DIR/embedded_migrations.rs` so they can be compiled into the binary.
+fn main() {
+    println!("cargo:rerun-if-changed=resources/db");
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED").is_none() {
+        return;
+    }
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let dir = Path::new(&manifest_dir).join("resources/db");
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .expect("Failed to read resources/db")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".sql"))
+        .collect();
+    names.sort();
+
+    let entries: String = names
+        .iter()
+        .map(|name| {
+            format!(
+                "    ({name:?}, include_str!({:?})),\n",
+                dir.join(name).display().to_string()
+            )
+        })
+        .collect();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(
+        Path::new(&out_dir).join("embedded_migrations.rs"),
+        format!("const EMBEDDED_MIGRATIONS: &[(&str, &str)] = &[\n{entries}];\n"),
+    )
+    .expect("Failed to write the embedded migrations");
+}
@@ -0,0 +1,6 @@
+DROP TRIGGER touch_abilities_on_tag_insert;
+DROP TRIGGER touch_abilities_on_tag_update;
+DROP TRIGGER touch_abilities_on_tag_delete;
+DROP TRIGGER touch_items_on_tag_insert;
+DROP TRIGGER touch_items_on_tag_update;
+DROP TRIGGER touch_items_on_tag_delete;
@@ -0,0 +1,36 @@
+-- Tag changes count as changes of the entity they belong to
+CREATE TRIGGER touch_abilities_on_tag_insert
+AFTER INSERT ON abilities_tags
+BEGIN
+    UPDATE abilities SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.ability_id;
+END;
+
+CREATE TRIGGER touch_abilities_on_tag_update
+AFTER UPDATE ON abilities_tags
+BEGIN
+    UPDATE abilities SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.ability_id;
+END;
+
+CREATE TRIGGER touch_abilities_on_tag_delete
+AFTER DELETE ON abilities_tags
+BEGIN
+    UPDATE abilities SET updated_at = CURRENT_TIMESTAMP WHERE id = OLD.ability_id;
+END;
+
+CREATE TRIGGER touch_items_on_tag_insert
+AFTER INSERT ON items_tags
+BEGIN
+    UPDATE items SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.item_id;
+END;
+
+CREATE TRIGGER touch_items_on_tag_update
+AFTER UPDATE ON items_tags
+BEGIN
+    UPDATE items SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.item_id;
+END;
+
+CREATE TRIGGER touch_items_on_tag_delete
+AFTER DELETE ON items_tags
+BEGIN
+    UPDATE items SET updated_at = CURRENT_TIMESTAMP WHERE id = OLD.item_id;
+END;
@@ -0,0 +1,10 @@
+DROP TRIGGER tombstone_abilities_on_delete;
+DROP TRIGGER tombstone_abilities_on_rename;
+DROP TRIGGER tombstone_items_on_delete;
+DROP TRIGGER tombstone_items_on_rename;
+
+DROP INDEX idx_tombstones_deleted_at;
+DROP INDEX idx_abilities_updated_at;
+DROP INDEX idx_items_updated_at;
+
+DROP TABLE tombstones;
@@ -0,0 +1,37 @@
+-- Slugs whose entity was purged or renamed, so clients syncing the changes can drop them.
+-- Entities in the trash are reported from their deleted_at instead.
+CREATE TABLE tombstones (
+  entity_type TEXT NOT NULL CHECK (entity_type IN ('ability', 'item')),
+  slug TEXT NOT NULL,
+  deleted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_tombstones_deleted_at ON tombstones(deleted_at);
+CREATE INDEX idx_abilities_updated_at ON abilities(updated_at);
+CREATE INDEX idx_items_updated_at ON items(updated_at);
+
+CREATE TRIGGER tombstone_abilities_on_delete
+AFTER DELETE ON abilities
+BEGIN
+    INSERT INTO tombstones (entity_type, slug) VALUES ('ability', OLD.slug);
+END;
+
+CREATE TRIGGER tombstone_abilities_on_rename
+AFTER UPDATE OF slug ON abilities
+WHEN NEW.slug IS NOT OLD.slug
+BEGIN
+    INSERT INTO tombstones (entity_type, slug) VALUES ('ability', OLD.slug);
+END;
+
+CREATE TRIGGER tombstone_items_on_delete
+AFTER DELETE ON items
+BEGIN
+    INSERT INTO tombstones (entity_type, slug) VALUES ('item', OLD.slug);
+END;
+
+CREATE TRIGGER tombstone_items_on_rename
+AFTER UPDATE OF slug ON items
+WHEN NEW.slug IS NOT OLD.slug
+BEGIN
+    INSERT INTO tombstones (entity_type, slug) VALUES ('item', OLD.slug);
+END;
@@ -0,0 +1,6 @@
+DROP INDEX idx_refresh_tokens_email;
+DROP INDEX idx_refresh_tokens_expires_at;
+DROP INDEX idx_revoked_tokens_expires_at;
+
+DROP TABLE revoked_tokens;
+DROP TABLE refresh_tokens;
@@ -0,0 +1,20 @@
+-- Refresh tokens, stored as their SHA-256 hashes. Each use replaces the token with a new one,
+-- so a revoked token that is used again was stolen and ends all of the sessions of the user.
+CREATE TABLE refresh_tokens (
+  token_hash TEXT NOT NULL PRIMARY KEY,
+  email TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMP NOT NULL,
+  revoked_at TIMESTAMP
+);
+
+-- Access tokens revoked before they expire, kept until they would have expired
+CREATE TABLE revoked_tokens (
+  jti TEXT NOT NULL PRIMARY KEY,
+  expires_at TIMESTAMP NOT NULL,
+  revoked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_refresh_tokens_email ON refresh_tokens(email);
+CREATE INDEX idx_refresh_tokens_expires_at ON refresh_tokens(expires_at);
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
@@ -0,0 +1,15 @@
+DROP TRIGGER sequence_tombstones_on_insert;
+DROP TRIGGER sequence_items_on_update;
+DROP TRIGGER sequence_items_on_insert;
+DROP TRIGGER sequence_abilities_on_update;
+DROP TRIGGER sequence_abilities_on_insert;
+
+DROP INDEX idx_tombstones_change_seq;
+DROP INDEX idx_items_change_seq;
+DROP INDEX idx_abilities_change_seq;
+
+ALTER TABLE tombstones DROP COLUMN change_seq;
+ALTER TABLE items DROP COLUMN change_seq;
+ALTER TABLE abilities DROP COLUMN change_seq;
+
+DROP TABLE change_sequence;
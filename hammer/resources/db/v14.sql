@@ -0,0 +1,52 @@
+-- A counter that every write to an item, an ability or a tombstone takes the next value of.
+-- The timestamps are those of the statements, so a long transaction that commits after a client
+-- synced would be missed by a client asking for the changes since then. SQLite has one writer
+-- at a time, so such a transaction always takes higher values than a reader already saw.
+CREATE TABLE change_sequence (
+  id INTEGER PRIMARY KEY CHECK (id = 1),
+  value INTEGER NOT NULL
+);
+INSERT INTO change_sequence (id, value) VALUES (1, 0);
+
+ALTER TABLE abilities ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tombstones ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_abilities_change_seq ON abilities(change_seq);
+CREATE INDEX idx_items_change_seq ON items(change_seq);
+CREATE INDEX idx_tombstones_change_seq ON tombstones(change_seq);
+
+CREATE TRIGGER sequence_abilities_on_insert
+AFTER INSERT ON abilities
+BEGIN
+    UPDATE change_sequence SET value = value + 1 WHERE id = 1;
+    UPDATE abilities SET change_seq = (SELECT value FROM change_sequence WHERE id = 1) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER sequence_abilities_on_update
+AFTER UPDATE ON abilities
+BEGIN
+    UPDATE change_sequence SET value = value + 1 WHERE id = 1;
+    UPDATE abilities SET change_seq = (SELECT value FROM change_sequence WHERE id = 1) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER sequence_items_on_insert
+AFTER INSERT ON items
+BEGIN
+    UPDATE change_sequence SET value = value + 1 WHERE id = 1;
+    UPDATE items SET change_seq = (SELECT value FROM change_sequence WHERE id = 1) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER sequence_items_on_update
+AFTER UPDATE ON items
+BEGIN
+    UPDATE change_sequence SET value = value + 1 WHERE id = 1;
+    UPDATE items SET change_seq = (SELECT value FROM change_sequence WHERE id = 1) WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER sequence_tombstones_on_insert
+AFTER INSERT ON tombstones
+BEGIN
+    UPDATE change_sequence SET value = value + 1 WHERE id = 1;
+    UPDATE tombstones SET change_seq = (SELECT value FROM change_sequence WHERE id = 1) WHERE rowid = NEW.rowid;
+END;
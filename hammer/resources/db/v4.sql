@@ -0,0 +1,6 @@
+-- Soft deletes
+ALTER TABLE abilities ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE items ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX idx_abilities_deleted_at ON abilities(deleted_at);
+CREATE INDEX idx_items_deleted_at ON items(deleted_at);
@@ -0,0 +1,23 @@
+-- Tag change proposals submitted by viewers and reviewed by editors
+CREATE TABLE tag_proposals (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  entity_type TEXT NOT NULL CHECK (entity_type IN ('item', 'ability')),
+  entity_slug TEXT NOT NULL,
+  comment TEXT,
+  author_email TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'rejected')),
+  reviewer_email TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  reviewed_at TIMESTAMP
+);
+
+CREATE TABLE tag_proposal_changes (
+  proposal_id INTEGER NOT NULL,
+  tag_name TEXT NOT NULL,
+  action TEXT NOT NULL CHECK (action IN ('add', 'remove')),
+  FOREIGN KEY (proposal_id) REFERENCES tag_proposals(id) ON DELETE CASCADE,
+  FOREIGN KEY (tag_name) REFERENCES tags(name) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tag_proposals_status ON tag_proposals(status);
+CREATE INDEX idx_tag_proposal_changes_proposal_id ON tag_proposal_changes(proposal_id);
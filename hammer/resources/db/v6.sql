@@ -0,0 +1,12 @@
+-- Provenance of entity-tag pairs: 'import', 'editor' or 'proposal'
+-- source_ref holds the import run, the editor email or the proposal id
+ALTER TABLE abilities_tags ADD COLUMN source TEXT NOT NULL DEFAULT 'import';
+ALTER TABLE abilities_tags ADD COLUMN source_ref TEXT;
+ALTER TABLE items_tags ADD COLUMN source TEXT NOT NULL DEFAULT 'import';
+ALTER TABLE items_tags ADD COLUMN source_ref TEXT;
+
+-- Human verification of entities
+ALTER TABLE abilities ADD COLUMN verified_at TIMESTAMP;
+ALTER TABLE abilities ADD COLUMN verified_by TEXT;
+ALTER TABLE items ADD COLUMN verified_at TIMESTAMP;
+ALTER TABLE items ADD COLUMN verified_by TEXT;
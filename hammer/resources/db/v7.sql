@@ -0,0 +1,18 @@
+-- Fields editors have locked against changes from quarry imports
+CREATE TABLE abilities_field_locks (
+  ability_id INTEGER NOT NULL,
+  field TEXT NOT NULL,
+  locked_by TEXT NOT NULL,
+  locked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (ability_id, field),
+  FOREIGN KEY (ability_id) REFERENCES abilities(id) ON DELETE CASCADE
+);
+
+CREATE TABLE items_field_locks (
+  item_id INTEGER NOT NULL,
+  field TEXT NOT NULL,
+  locked_by TEXT NOT NULL,
+  locked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (item_id, field),
+  FOREIGN KEY (item_id) REFERENCES items(id) ON DELETE CASCADE
+);
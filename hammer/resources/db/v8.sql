@@ -0,0 +1,34 @@
+-- Quarry import runs and the entity changes they made
+CREATE TABLE import_runs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  items_path TEXT NOT NULL,
+  items_hash TEXT NOT NULL,
+  abilities_path TEXT NOT NULL,
+  abilities_hash TEXT NOT NULL,
+  items_created INTEGER NOT NULL DEFAULT 0,
+  items_updated INTEGER NOT NULL DEFAULT 0,
+  items_unchanged INTEGER NOT NULL DEFAULT 0,
+  items_removed INTEGER NOT NULL DEFAULT 0,
+  abilities_created INTEGER NOT NULL DEFAULT 0,
+  abilities_updated INTEGER NOT NULL DEFAULT 0,
+  abilities_unchanged INTEGER NOT NULL DEFAULT 0,
+  abilities_removed INTEGER NOT NULL DEFAULT 0,
+  conflicts INTEGER NOT NULL DEFAULT 0,
+  started_at TIMESTAMP NOT NULL,
+  finished_at TIMESTAMP,
+  rolled_back_at TIMESTAMP
+);
+
+-- Snapshots are JSON documents of the entity before and after the change
+CREATE TABLE import_run_changes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_id INTEGER NOT NULL,
+  entity_type TEXT NOT NULL CHECK (entity_type IN ('item', 'ability')),
+  slug TEXT NOT NULL,
+  action TEXT NOT NULL CHECK (action IN ('created', 'updated', 'removed')),
+  before TEXT,
+  after TEXT,
+  FOREIGN KEY (run_id) REFERENCES import_runs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_import_run_changes_run_id ON import_run_changes(run_id);
@@ -0,0 +1,5 @@
+DROP INDEX idx_abilities_game_data_id;
+DROP INDEX idx_items_game_data_id;
+
+ALTER TABLE abilities DROP COLUMN game_data_id;
+ALTER TABLE items DROP COLUMN game_data_id;
@@ -0,0 +1,6 @@
+-- Stable IDs of the game data objects the entities were reconciled with
+ALTER TABLE abilities ADD COLUMN game_data_id TEXT;
+ALTER TABLE items ADD COLUMN game_data_id TEXT;
+
+CREATE UNIQUE INDEX idx_abilities_game_data_id ON abilities(game_data_id) WHERE game_data_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_items_game_data_id ON items(game_data_id) WHERE game_data_id IS NOT NULL;
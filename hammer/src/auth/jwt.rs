@@ -1,4 +1,8 @@
-use crate::{CONFIG, auth::user::Role, error::Error};
+use crate::{
+    CONFIG,
+    auth::user::{Role, User},
+    error::Error,
+};
 use hmac::{Hmac, Mac};
 use jwt::{SignWithKey, VerifyWithKey};
 use serde::{Deserialize, Serialize};
@@ -7,10 +11,31 @@ use sha2::Sha384;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(super) struct MyJWT {
     pub(super) email: String,
+    /// The role when the token was issued. Requests are authorized with the current role of
+    /// the user instead.
     pub(super) role: Role,
+    /// When the token was issued, in seconds since the epoch.
+    pub(super) iat: i64,
+    /// When the token expires, in seconds since the epoch.
+    pub(super) exp: i64,
+    /// The ID the token is revoked by.
+    pub(super) jti: String,
 }
 
 impl MyJWT {
+    /// A token for the user that expires after the configured lifetime.
+    pub(super) fn new(user: User) -> Result<Self, Error> {
+        let iat = chrono::Utc::now().timestamp();
+        let lifetime = i64::try_from(CONFIG.access_token_lifetime_seconds).unwrap_or(i64::MAX);
+        Ok(Self {
+            email: user.email,
+            role: user.role,
+            iat,
+            exp: iat.saturating_add(lifetime),
+            jti: super::random_token(16)?,
+        })
+    }
+
     pub(super) fn signed_token(&self) -> Result<String, Error> {
         let secret = CONFIG.auth_secret.as_bytes();
         let key: Hmac<Sha384> = Hmac::new_from_slice(secret)?;
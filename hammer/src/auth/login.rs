@@ -1,61 +1,66 @@
-use axum::extract::Json;
+use axum::extract::{Json, State};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     auth::{
         jwt::MyJWT,
+        oidc, refresh,
         user::{Role, User},
     },
-    db,
+    db::{self, Pool},
     error::Error,
 };
 
+/// The ID token from Google, or another OpenID Connect provider, of the user logging in.
 #[derive(Clone, Debug, Deserialize)]
-#[allow(dead_code)]
-pub(super) struct UserInfo {
-    sub: String,
-    name: String,
-    given_name: String,
-    family_name: String,
-    picture: String,
-    email: String,
-    email_verified: bool,
+pub(super) struct LoginRequest {
+    id_token: String,
 }
 
+/// The tokens issued to a user logging in or refreshing their tokens.
 #[derive(Debug, Serialize)]
 pub(super) struct LoginResponse {
     jwt: String,
+    /// When the `jwt` expires, in seconds since the epoch.
+    expires_at: i64,
+    /// Exchanged for new tokens at `/auth/refresh` once the `jwt` expires.
+    refresh_token: String,
     email: String,
     role: Role,
 }
 
 #[axum::debug_handler]
-#[tracing::instrument(level = "trace")]
-pub(super) async fn login(Json(user_info): Json<UserInfo>) -> Result<Json<LoginResponse>, Error> {
-    let conn = db::get_connection()?;
-    let mut stmt = conn.prepare_cached("SELECT email, role FROM users WHERE email=?1")?;
-    let mut rows = stmt.query(rusqlite::params![&user_info.email])?;
-    let user: User;
-    if let Some(row) = rows.next()? {
-        tracing::trace!("User found in the database: {:?}", row);
-        let email: String = row.get(0)?;
-        let role: Role = row.get(1)?;
-        user = User { email, role }
-    } else {
-        user = User {
-            email: user_info.email,
-            role: Role::Viewer,
-        }
-    }
-    let jwt = MyJWT {
-        email: user.email,
-        role: user.role,
-    };
+#[tracing::instrument(level = "trace", skip(request))]
+pub(super) async fn login(
+    State(pool): State<Pool>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, Error> {
+    let claims = oidc::verify(&request.id_token).await?;
+    let response = db::run(&pool, move |conn| {
+        let role = db::user::find_role(&claims.email, conn)?;
+        issue_tokens(
+            User {
+                email: claims.email,
+                role,
+            },
+            conn,
+        )
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+/// Issues an access token and a refresh token to the user.
+pub(super) fn issue_tokens(user: User, conn: &Connection) -> Result<LoginResponse, Error> {
+    let jwt = MyJWT::new(user)?;
     tracing::trace!("Creating JWT for user: {:?}", jwt);
-    let token = jwt.signed_token()?;
-    Ok(Json(LoginResponse {
-        jwt: token,
+    let refresh_token = refresh::new_refresh_token(&jwt.email, conn)?;
+    Ok(LoginResponse {
+        jwt: jwt.signed_token()?,
+        expires_at: jwt.exp,
+        refresh_token,
         email: jwt.email,
         role: jwt.role,
-    }))
+    })
 }
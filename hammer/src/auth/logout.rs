@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Json, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::{middleware::get_token, refresh::hash_token},
+    db::{self, Pool},
+    error::{Error, ErrorType},
+};
+
+#[derive(Debug, Deserialize)]
+pub(super) struct LogoutRequest {
+    refresh_token: Option<String>,
+}
+
+/// Revokes the access token of the `Authorization` header and the refresh token of the body.
+/// An expired access token is ignored if there is a refresh token to revoke.
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace", skip_all)]
+pub(super) async fn logout(
+    State(pool): State<Pool>,
+    headers: HeaderMap,
+    request: Option<Json<LogoutRequest>>,
+) -> Result<StatusCode, Error> {
+    let token = get_token(&headers).map(str::to_string);
+    let refresh_token = request.and_then(|Json(request)| request.refresh_token);
+    if token.is_none() && refresh_token.is_none() {
+        return Err(Error(
+            String::from("There is no token to revoke"),
+            ErrorType::Forbidden,
+        ));
+    }
+    db::run(&pool, move |conn| {
+        if let Some(token) = token {
+            match super::verify_token(&token, conn) {
+                Ok(jwt) => db::token::revoke(&jwt.jti, jwt.exp, conn)?,
+                Err(err) if refresh_token.is_none() => return Err(err),
+                Err(_) => {}
+            }
+        }
+        if let Some(refresh_token) = refresh_token {
+            db::token::delete_refresh_token(&hash_token(&refresh_token), conn)?;
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,33 +1,83 @@
 use crate::{
-    auth::user::Role,
+    auth::user::{Role, User},
+    db::{self, Pool},
     error::{Error, ErrorType},
 };
 
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::{HeaderMap, header::AUTHORIZATION},
     middleware::Next,
     response::Response,
 };
 
+/// Lets through any user with a valid token, including viewers.
+pub(crate) async fn login_required(
+    State(pool): State<Pool>,
+    headers: HeaderMap,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    authorize(&pool, &headers, &mut request, |_| true).await?;
+    Ok(next.run(request).await)
+}
+
 pub(crate) async fn auth_required(
+    State(pool): State<Pool>,
+    headers: HeaderMap,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    authorize(&pool, &headers, &mut request, |role| {
+        matches!(role, Role::Admin | Role::Editor)
+    })
+    .await?;
+    Ok(next.run(request).await)
+}
+
+pub(crate) async fn admin_required(
+    State(pool): State<Pool>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, Error> {
-    match get_token(&headers) {
-        Some(token) if is_token_valid(token)? => {
-            let response = next.run(request).await;
-            Ok(response)
+    authorize(&pool, &headers, &mut request, |role| {
+        matches!(role, Role::Admin)
+    })
+    .await?;
+    Ok(next.run(request).await)
+}
+
+/// Verifies the token and makes the authenticated [`User`], with their current role, available
+/// to the handlers as a request extension.
+async fn authorize(
+    pool: &Pool,
+    headers: &HeaderMap,
+    request: &mut Request,
+    is_allowed: fn(&Role) -> bool,
+) -> Result<(), Error> {
+    match get_token(headers) {
+        Some(token) => {
+            let token = token.to_string();
+            let jwt = db::run(pool, move |conn| super::verify_token(&token, conn)).await?;
+            if !is_allowed(&jwt.role) {
+                return Err(invalid_token());
+            }
+            request.extensions_mut().insert(User {
+                email: jwt.email,
+                role: jwt.role,
+            });
+            Ok(())
         }
-        _ => Err(Error(
-            String::from("The token is invalid"),
-            ErrorType::Forbidden,
-        )),
+        None => Err(invalid_token()),
     }
 }
 
-fn get_token(headers: &HeaderMap) -> Option<&str> {
+fn invalid_token() -> Error {
+    Error(String::from("The token is invalid"), ErrorType::Forbidden)
+}
+
+pub(super) fn get_token(headers: &HeaderMap) -> Option<&str> {
     headers.get(AUTHORIZATION).and_then(|auth_value| {
         let value: &str = auth_value.to_str().unwrap_or("");
         let (_, token) = value.split_once(' ')?;
@@ -35,8 +85,3 @@ fn get_token(headers: &HeaderMap) -> Option<&str> {
         Some(token)
     })
 }
-
-fn is_token_valid(token: &str) -> Result<bool, Error> {
-    let jwt = super::verify_token(token)?;
-    Ok(matches!(jwt.role, Role::Admin | Role::Editor))
-}
@@ -1,21 +1,94 @@
 mod jwt;
 mod login;
+mod logout;
 mod middleware;
+mod oidc;
+mod refresh;
 mod routes;
 mod user;
 mod verify;
 
 use ::hmac::Mac;
 use ::jwt::VerifyWithKey;
-pub(crate) use middleware::auth_required;
+pub(crate) use middleware::{admin_required, auth_required, login_required};
 pub(crate) use routes::auth_routes;
+use rusqlite::Connection;
+pub(crate) use user::{Role, User};
 
-fn verify_token(token: &str) -> Result<jwt::MyJWT, crate::error::Error> {
+use crate::db;
+use crate::error::{Error, ErrorType};
+
+/// Verifies the signature and the expiry of the token and that it was not revoked. The role
+/// of the returned token is the current one of the user, so role changes apply to the tokens
+/// issued before them.
+fn verify_token(token: &str, conn: &Connection) -> Result<jwt::MyJWT, Error> {
     let secret = crate::CONFIG.auth_secret.as_bytes();
     let key: hmac::Hmac<sha2::Sha384> = hmac::Hmac::new_from_slice(secret)
         .inspect_err(|err| tracing::debug!("Hmac algorithm creation failed with: {err:?}"))?;
-    let my_jwt: jwt::MyJWT = token
+    let mut my_jwt: jwt::MyJWT = token
         .verify_with_key(&key)
         .inspect_err(|err| tracing::debug!("Verification failed with error: {err:?}"))?;
+    if my_jwt.exp <= chrono::Utc::now().timestamp() {
+        return Err(Error(
+            String::from("The token has expired"),
+            ErrorType::Forbidden,
+        ));
+    }
+    if db::token::is_revoked(&my_jwt.jti, conn)? {
+        return Err(Error(
+            String::from("The token was revoked"),
+            ErrorType::Forbidden,
+        ));
+    }
+    my_jwt.role = db::user::find_role(&my_jwt.email, conn)?;
     Ok(my_jwt)
 }
+
+/// A random hex token of `bytes` bytes.
+fn random_token(bytes: usize) -> Result<String, Error> {
+    let mut buffer = vec![0u8; bytes];
+    getrandom::getrandom(&mut buffer).map_err(|err| {
+        Error(
+            format!("Failed to generate a token: {err}"),
+            ErrorType::Cryptography,
+        )
+    })?;
+    Ok(buffer.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::get_test_connection;
+
+    #[test]
+    fn test_tokens_expire_are_revoked_and_follow_role_changes() {
+        let conn = get_test_connection();
+        db::user::insert("editor@example.com", &Role::Editor, &conn).unwrap();
+        let user = User {
+            email: "editor@example.com".to_string(),
+            role: Role::Editor,
+        };
+        let jwt = jwt::MyJWT::new(user.clone()).unwrap();
+        let token = jwt.signed_token().unwrap();
+        assert!(matches!(
+            verify_token(&token, &conn).unwrap().role,
+            Role::Editor
+        ));
+
+        db::user::set_role("editor@example.com", &Role::Viewer, &conn).unwrap();
+        assert!(matches!(
+            verify_token(&token, &conn).unwrap().role,
+            Role::Viewer
+        ));
+
+        db::token::revoke(&jwt.jti, jwt.exp, &conn).unwrap();
+        assert!(verify_token(&token, &conn).is_err());
+
+        let expired = jwt::MyJWT {
+            exp: jwt.iat - 1,
+            ..jwt::MyJWT::new(user).unwrap()
+        };
+        assert!(verify_token(&expired.signed_token().unwrap(), &conn).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation,
+    jwk::{AlgorithmParameters, JwkSet},
+};
+use serde::Deserialize;
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::error::{Error, ErrorType};
+use crate::models::CONFIG;
+
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+/// Google also issues ID tokens with the issuer without the scheme.
+const GOOGLE_ISSUER_WITHOUT_SCHEME: &str = "accounts.google.com";
+
+/// How long the fetched keys are used. Providers publish new keys well before they sign
+/// with them.
+const KEYS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+/// A token signed with an unknown key makes the keys be fetched again, but not more often
+/// than this, so bad tokens cannot make the server flood the provider.
+const KEYS_MIN_AGE: Duration = Duration::from_secs(60);
+
+static KEYS: RwLock<Option<FetchedKeys>> = RwLock::const_new(None);
+/// The keys of `oidc_jwks_path`, read at the first login.
+static FILE_KEYS: OnceCell<JwkSet> = OnceCell::const_new();
+
+#[derive(Clone)]
+struct FetchedKeys {
+    fetched_at: Instant,
+    keys: JwkSet,
+}
+
+/// The claims of a verified ID token that the login uses.
+#[derive(Debug, Deserialize)]
+pub(super) struct IdClaims {
+    pub(super) email: String,
+    #[serde(default)]
+    pub(super) email_verified: bool,
+}
+
+/// Verifies the signature, audience, issuer and expiry of an ID token against the configured
+/// keys, and that its email address is verified.
+pub(super) async fn verify(token: &str) -> Result<IdClaims, Error> {
+    let Some(audience) = CONFIG.oidc_audience.as_deref() else {
+        return Err(Error(
+            "The login is not configured, oidc_audience is not set".to_string(),
+            ErrorType::Unavailable,
+        ));
+    };
+    let kid = jsonwebtoken::decode_header(token)
+        .map_err(invalid_token)?
+        .kid;
+    let mut keys = find_keys(false).await?;
+    if let Some(kid) = &kid
+        && keys.find(kid).is_none()
+    {
+        tracing::debug!("No key {kid} among the keys, fetching them again");
+        keys = find_keys(true).await?;
+    }
+    verify_with_keys(token, &keys, audience, &CONFIG.oidc_issuer)
+}
+
+fn verify_with_keys(
+    token: &str,
+    keys: &JwkSet,
+    audience: &str,
+    issuer: &str,
+) -> Result<IdClaims, Error> {
+    let header = jsonwebtoken::decode_header(token).map_err(invalid_token)?;
+    let jwk = match &header.kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| invalid_token("No key matches the token"))?;
+    // A shared secret in a public key set would let anyone sign tokens
+    if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_))
+        || matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        )
+    {
+        return Err(invalid_token("The token is not signed with a public key"));
+    }
+    let key = DecodingKey::from_jwk(jwk).map_err(invalid_token)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[audience]);
+    if issuer == GOOGLE_ISSUER {
+        validation.set_issuer(&[GOOGLE_ISSUER, GOOGLE_ISSUER_WITHOUT_SCHEME]);
+    } else {
+        validation.set_issuer(&[issuer]);
+    }
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+    let claims: IdClaims = jsonwebtoken::decode(token, &key, &validation)
+        .map_err(invalid_token)?
+        .claims;
+    if !claims.email_verified {
+        return Err(Error(
+            format!("The email address {} is not verified", claims.email),
+            ErrorType::Forbidden,
+        ));
+    }
+    Ok(claims)
+}
+
+/// The keys from the configured file, or the fetched ones. Fetches them if they are too old,
+/// or if `refresh` is set and they were not just fetched.
+async fn find_keys(refresh: bool) -> Result<JwkSet, Error> {
+    if let Some(path) = &CONFIG.oidc_jwks_path {
+        let keys = FILE_KEYS
+            .get_or_try_init(|| async {
+                let json = tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|err| format!("Failed to read the keys from {path}: {err}"))?;
+                serde_json::from_str(&json).map_err(|err| {
+                    Error::from(format!("Failed to parse the keys in {path}: {err}"))
+                })
+            })
+            .await?;
+        return Ok(keys.clone());
+    }
+
+    let min_age = if refresh { KEYS_MIN_AGE } else { KEYS_MAX_AGE };
+    if let Some(fetched) = KEYS.read().await.as_ref()
+        && fetched.fetched_at.elapsed() < min_age
+    {
+        return Ok(fetched.keys.clone());
+    }
+    let mut cached = KEYS.write().await;
+    // Another login may have fetched them while this one waited for the lock
+    if let Some(fetched) = cached.as_ref()
+        && fetched.fetched_at.elapsed() < KEYS_MIN_AGE
+    {
+        return Ok(fetched.keys.clone());
+    }
+    let keys = fetch_keys(&CONFIG.oidc_jwks_url).await?;
+    *cached = Some(FetchedKeys {
+        fetched_at: Instant::now(),
+        keys: keys.clone(),
+    });
+    Ok(keys)
+}
+
+async fn fetch_keys(url: &str) -> Result<JwkSet, Error> {
+    let unavailable = |err: reqwest::Error| {
+        Error(
+            format!("Failed to fetch the keys from {url}: {err}"),
+            ErrorType::Unavailable,
+        )
+    };
+    let json = reqwest::get(url)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(unavailable)?
+        .text()
+        .await
+        .map_err(unavailable)?;
+    tracing::info!("Fetched the keys that sign the ID tokens from {url}");
+    serde_json::from_str(&json)
+        .map_err(|err| format!("Failed to parse the keys from {url}: {err}").into())
+}
+
+fn invalid_token(err: impl std::fmt::Display) -> Error {
+    tracing::debug!("The ID token is invalid. {err}");
+    Error(
+        format!("The ID token is invalid: {err}"),
+        ErrorType::Forbidden,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    const AUDIENCE: &str = "client-id.apps.googleusercontent.com";
+
+    fn token(kid: &str, claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        // Only signs the tokens of the tests, its public key is in oidc-jwks.json
+        let key = EncodingKey::from_rsa_pem(include_bytes!(
+            "../../resources/test/oidc-test-only-private-key.pem"
+        ))
+        .unwrap();
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+
+    fn claims(changes: serde_json::Value) -> serde_json::Value {
+        let mut claims = serde_json::json!({
+            "iss": "accounts.google.com",
+            "aud": AUDIENCE,
+            "sub": "1234",
+            "email": "editor@example.com",
+            "email_verified": true,
+            "iat": chrono::Utc::now().timestamp(),
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        });
+        for (claim, value) in changes.as_object().unwrap() {
+            claims[claim] = value.clone();
+        }
+        claims
+    }
+
+    #[test]
+    fn test_only_valid_id_tokens_are_accepted() {
+        let keys: JwkSet =
+            serde_json::from_str(include_str!("../../resources/test/oidc-jwks.json")).unwrap();
+        let verify = |token: &str| verify_with_keys(token, &keys, AUDIENCE, GOOGLE_ISSUER);
+
+        let valid = token("test-key", claims(serde_json::json!({})));
+        assert_eq!(verify(&valid).unwrap().email, "editor@example.com");
+
+        let hour_ago = chrono::Utc::now().timestamp() - 3600;
+        for invalid in [
+            token("other-key", claims(serde_json::json!({}))),
+            token(
+                "test-key",
+                claims(serde_json::json!({"aud": "other-client"})),
+            ),
+            token(
+                "test-key",
+                claims(serde_json::json!({"iss": "https://evil.example.com"})),
+            ),
+            token("test-key", claims(serde_json::json!({"exp": hour_ago}))),
+            token(
+                "test-key",
+                claims(serde_json::json!({"email_verified": false})),
+            ),
+            format!("{}x", &valid[..valid.len() - 1]),
+        ] {
+            assert!(verify(&invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use axum::extract::{Json, State};
+use rusqlite::Connection;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    CONFIG,
+    auth::{
+        login::{LoginResponse, issue_tokens},
+        user::User,
+    },
+    db::{self, Pool},
+    error::{Error, ErrorType},
+};
+
+#[derive(Deserialize)]
+pub(super) struct RefreshRequest {
+    refresh_token: String,
+}
+
+/// Exchanges a refresh token for new tokens with the current role of the user. The refresh
+/// token can only be used once, except by parallel requests within a short grace period.
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace", skip_all)]
+pub(super) async fn refresh(
+    State(pool): State<Pool>,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, Error> {
+    let response = db::run(&pool, move |conn| {
+        let token_hash = hash_token(&request.refresh_token);
+        let Some(email) = db::token::take_refresh_token(&token_hash, conn)? else {
+            return Err(Error(
+                String::from("The refresh token is invalid"),
+                ErrorType::Forbidden,
+            ));
+        };
+        let role = db::user::find_role(&email, conn)?;
+        issue_tokens(User { email, role }, conn)
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+/// Creates a refresh token for the user. Only its hash is stored.
+pub(super) fn new_refresh_token(email: &str, conn: &Connection) -> Result<String, Error> {
+    let token = super::random_token(32)?;
+    db::token::insert_refresh_token(
+        &hash_token(&token),
+        email,
+        CONFIG.refresh_token_lifetime_seconds,
+        conn,
+    )?;
+    Ok(token)
+}
+
+pub(super) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -1,14 +1,17 @@
-use super::{login::login, verify::verify};
+use super::{login::login, logout::logout, refresh::refresh, verify::verify};
+use crate::db::Pool;
 use axum::{
     Router,
     routing::{get, post},
 };
 
-pub(crate) fn auth_routes() -> Router<()> {
+pub(crate) fn auth_routes() -> Router<Pool> {
     Router::new().nest(
         "/auth",
         Router::new()
             .route("/verify", get(verify))
-            .route("/login", post(login)),
+            .route("/login", post(login))
+            .route("/refresh", post(refresh))
+            .route("/logout", post(logout)),
     )
 }
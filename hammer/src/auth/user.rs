@@ -6,18 +6,28 @@ use rusqlite::types::{FromSql, FromSqlError};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
-pub(super) struct User {
-    pub(super) email: String,
-    pub(super) role: Role,
+pub(crate) struct User {
+    pub(crate) email: String,
+    pub(crate) role: Role,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub(super) enum Role {
+#[derive(Clone, Debug, Serialize, Deserialize, clap::ValueEnum)]
+pub(crate) enum Role {
     Editor,
     Admin,
     Viewer,
 }
 
+impl Role {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+            Role::Viewer => "viewer",
+        }
+    }
+}
+
 impl FromStr for Role {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
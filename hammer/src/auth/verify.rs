@@ -1,5 +1,9 @@
-use crate::{auth::jwt::MyJWT, error::Error};
-use axum::extract::{Json, Query};
+use crate::{
+    auth::jwt::MyJWT,
+    db::{self, Pool},
+    error::Error,
+};
+use axum::extract::{Json, Query, State};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -8,9 +12,15 @@ pub(super) struct VerifyParams {
 }
 
 #[axum::debug_handler]
-#[tracing::instrument(level = "trace")]
-pub(super) async fn verify(Query(params): Query<VerifyParams>) -> Result<Json<MyJWT>, Error> {
-    Ok(Json(super::verify_token(&params.auth_token).inspect_err(
-        |_| tracing::error!("No auth_token parameter"),
-    )?))
+#[tracing::instrument(level = "trace", skip_all)]
+pub(super) async fn verify(
+    State(pool): State<Pool>,
+    Query(params): Query<VerifyParams>,
+) -> Result<Json<MyJWT>, Error> {
+    let jwt = db::run(&pool, move |conn| {
+        super::verify_token(&params.auth_token, conn)
+    })
+    .await
+    .inspect_err(|err| tracing::debug!("The token is invalid. {err:?}"))?;
+    Ok(Json(jwt))
 }
@@ -0,0 +1,522 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::auth::Role;
+use crate::db;
+use crate::error::Error;
+use crate::import_from_quarry::{
+    InputFormat, SyncOptions, check_tag_vocabulary, import_to_db, rollback_import_run, sync_tags,
+    validate_file,
+};
+use crate::models::{AbbreviatedAbility, CONFIG, ConfigOverrides, JsonItem, Strictness};
+use crate::resources::{self, Resource};
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "The backend of cracked-pillars")]
+pub(crate) struct Cli {
+    #[command(flatten)]
+    pub(crate) config: ConfigArgs,
+    /// Defaults to `serve`.
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+/// Overrides of the configuration file. They take precedence over the file and the
+/// `HAMMER_*` environment variables.
+#[derive(Debug, Args)]
+pub(crate) struct ConfigArgs {
+    /// The configuration file. Defaults to HAMMER_CONFIG_FILE or ./hammer.toml.
+    #[arg(long, global = true)]
+    config: Option<String>,
+    #[arg(long, global = true)]
+    abilities_path: Option<String>,
+    #[arg(long, global = true)]
+    items_path: Option<String>,
+    #[arg(long, global = true)]
+    db_path: Option<String>,
+    #[arg(long, global = true)]
+    db_migrations: Option<String>,
+    #[arg(long, global = true)]
+    auth_secret: Option<String>,
+    #[arg(long, global = true)]
+    import_conflicts_path: Option<String>,
+    #[arg(long, global = true)]
+    tags_path: Option<String>,
+    #[arg(long, global = true)]
+    quarry_csv_dir: Option<String>,
+    #[arg(long, global = true)]
+    game_data_dir: Option<String>,
+    #[arg(long, global = true)]
+    items_schema_path: Option<String>,
+    #[arg(long, global = true)]
+    abilities_schema_path: Option<String>,
+    #[arg(long, global = true, value_enum)]
+    import_strictness: Option<Strictness>,
+    #[arg(long, global = true)]
+    bind_address: Option<String>,
+    #[arg(long, global = true)]
+    port: Option<u16>,
+    #[arg(long, global = true)]
+    tls_cert_path: Option<String>,
+    #[arg(long, global = true)]
+    tls_key_path: Option<String>,
+    #[arg(long, global = true)]
+    unix_socket_path: Option<String>,
+    #[arg(long, global = true)]
+    seed_empty_db: Option<bool>,
+    #[arg(long, global = true)]
+    db_busy_timeout_ms: Option<u64>,
+    #[arg(long, global = true)]
+    db_pool_size: Option<u32>,
+    #[arg(long, global = true)]
+    tag_index: Option<bool>,
+    #[arg(long, global = true)]
+    response_cache_size: Option<usize>,
+    #[arg(long, global = true)]
+    oidc_audience: Option<String>,
+    #[arg(long, global = true)]
+    oidc_issuer: Option<String>,
+    #[arg(long, global = true)]
+    oidc_jwks_url: Option<String>,
+    #[arg(long, global = true)]
+    oidc_jwks_path: Option<String>,
+    #[arg(long, global = true)]
+    access_token_lifetime_seconds: Option<u64>,
+    #[arg(long, global = true)]
+    refresh_token_lifetime_seconds: Option<u64>,
+}
+
+impl From<ConfigArgs> for ConfigOverrides {
+    fn from(args: ConfigArgs) -> Self {
+        let fields = [
+            ("abilities_path", args.abilities_path),
+            ("items_path", args.items_path),
+            ("db_path", args.db_path),
+            ("db_migrations", args.db_migrations),
+            ("auth_secret", args.auth_secret),
+            ("import_conflicts_path", args.import_conflicts_path),
+            ("tags_path", args.tags_path),
+            ("quarry_csv_dir", args.quarry_csv_dir),
+            ("game_data_dir", args.game_data_dir),
+            ("items_schema_path", args.items_schema_path),
+            ("abilities_schema_path", args.abilities_schema_path),
+            (
+                "import_strictness",
+                args.import_strictness
+                    .map(|value| value.as_str().to_string()),
+            ),
+            ("bind_address", args.bind_address),
+            ("port", to_string(args.port)),
+            ("tls_cert_path", args.tls_cert_path),
+            ("tls_key_path", args.tls_key_path),
+            ("unix_socket_path", args.unix_socket_path),
+            ("seed_empty_db", to_string(args.seed_empty_db)),
+            ("db_busy_timeout_ms", to_string(args.db_busy_timeout_ms)),
+            ("db_pool_size", to_string(args.db_pool_size)),
+            ("tag_index", to_string(args.tag_index)),
+            ("response_cache_size", to_string(args.response_cache_size)),
+            ("oidc_audience", args.oidc_audience),
+            ("oidc_issuer", args.oidc_issuer),
+            ("oidc_jwks_url", args.oidc_jwks_url),
+            ("oidc_jwks_path", args.oidc_jwks_path),
+            (
+                "access_token_lifetime_seconds",
+                to_string(args.access_token_lifetime_seconds),
+            ),
+            (
+                "refresh_token_lifetime_seconds",
+                to_string(args.refresh_token_lifetime_seconds),
+            ),
+        ];
+        Self {
+            config_file: args.config,
+            fields: fields
+                .into_iter()
+                .filter_map(|(field, value)| value.map(|value| (field, value)))
+                .collect(),
+        }
+    }
+}
+
+fn to_string(value: Option<impl ToString>) -> Option<String> {
+    value.map(|value| value.to_string())
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Runs the HTTP server.
+    Serve,
+    /// Shows or applies the database migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Imports the quarry output into the database.
+    Import(ImportArgs),
+    /// Lists or rolls back import runs.
+    #[command(subcommand)]
+    ImportRuns(ImportRunsCommand),
+    /// Exports the items and abilities as JSON.
+    Export(ExportArgs),
+    /// Validates a quarry JSON file against its JSON Schema.
+    Validate { file: String },
+    /// Synchronizes the tag vocabulary with the tag list.
+    SyncTags,
+    /// Deletes the database.
+    ResetDb {
+        /// Skips the confirmation prompt.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Manages the users and their roles.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Prints the configuration and checks the paths in it.
+    CheckConfig,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum MigrateCommand {
+    /// Lists the migrations and whether they have been applied.
+    Status,
+    /// Applies the pending migrations.
+    Up,
+    /// Reverts the last applied migrations with their `.down.sql` files.
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ImportArgs {
+    #[arg(long, value_enum, default_value_t)]
+    format: InputFormat,
+    /// Computes the summary without writing anything to the database.
+    #[arg(long)]
+    dry_run: bool,
+    /// Moves entities that are missing from the quarry output to the trash.
+    #[arg(long)]
+    remove_missing: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum ImportRunsCommand {
+    List,
+    Rollback { id: i64 },
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ExportArgs {
+    /// The directory to write items.json and abilities.json to.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum UsersCommand {
+    Add {
+        email: String,
+        #[arg(long, value_enum, default_value = "viewer")]
+        role: Role,
+    },
+    SetRole {
+        email: String,
+        #[arg(value_enum)]
+        role: Role,
+    },
+    List,
+}
+
+/// Applies the pending migrations and warns when the tag vocabulary drifted.
+pub(crate) fn prepare_db() -> Result<(), Error> {
+    let conn = db::get_connection()?;
+    db::synchronize_db(&conn)?;
+    match check_tag_vocabulary(&conn) {
+        Ok(disagreements) => disagreements
+            .iter()
+            .for_each(|disagreement| tracing::warn!("{disagreement}")),
+        Err(e) => tracing::warn!("Failed to check the tag vocabulary: {e:?}"),
+    }
+    Ok(())
+}
+
+/// Imports the quarry output if the database has no items and no abilities yet.
+pub(crate) fn seed_empty_db() -> Result<(), Error> {
+    let conn = db::get_connection()?;
+    if !CONFIG.seed_empty_db || !db::is_empty(&conn)? {
+        return Ok(());
+    }
+    tracing::info!("The database is empty, importing the quarry output");
+    let summary = import_to_db(SyncOptions::default())?;
+    tracing::info!("Seeded the database. {summary}");
+    Ok(())
+}
+
+/// Runs every command except `serve`.
+pub(crate) fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Serve => unreachable!("The server is started by main"),
+        Command::Migrate(MigrateCommand::Status) => migration_status(),
+        Command::Migrate(MigrateCommand::Up) => prepare_db(),
+        Command::Migrate(MigrateCommand::Down { steps }) => {
+            let conn = db::get_connection()?;
+            for name in db::migrate_down(&conn, steps)? {
+                println!("Reverted {name}");
+            }
+            Ok(())
+        }
+        Command::Import(args) => {
+            prepare_db()?;
+            let summary = import_to_db(SyncOptions {
+                format: args.format,
+                dry_run: args.dry_run,
+                remove_missing: args.remove_missing,
+            })?;
+            tracing::info!("Successfully synchronized abilities and items from the quarry output");
+            println!("{summary}");
+            Ok(())
+        }
+        Command::ImportRuns(ImportRunsCommand::List) => {
+            prepare_db()?;
+            let conn = db::get_connection()?;
+            for run in db::import_run::find_all(&conn)? {
+                println!("{run}");
+            }
+            Ok(())
+        }
+        Command::ImportRuns(ImportRunsCommand::Rollback { id }) => {
+            prepare_db()?;
+            let (summary, _) = rollback_import_run(id, "cli", &mut db::get_connection()?)?;
+            println!("{summary}");
+            Ok(())
+        }
+        Command::Export(args) => {
+            prepare_db()?;
+            export(&args.output_dir)
+        }
+        Command::Validate { file } => validate(&file),
+        Command::SyncTags => {
+            prepare_db()?;
+            println!("Tags: {}", sync_tags()?);
+            Ok(())
+        }
+        Command::ResetDb { yes } => reset_db(yes),
+        Command::Users(command) => {
+            prepare_db()?;
+            users(command)
+        }
+        Command::CheckConfig => check_config(),
+    }
+}
+
+fn migration_status() -> Result<(), Error> {
+    let conn = db::get_connection()?;
+    let migrations = db::migration_status(&conn)?;
+    for migration in &migrations {
+        let status = match (migration.applied, migration.modified) {
+            (true, true) => "modified",
+            (true, false) => "applied",
+            (false, _) => "pending",
+        };
+        println!(
+            "{status}\t{}\t{}\t{}",
+            migration.name,
+            migration.applied_at.as_deref().unwrap_or("-"),
+            if migration.reversible {
+                "reversible"
+            } else {
+                "irreversible"
+            }
+        );
+    }
+    let pending = migrations.iter().filter(|m| !m.applied).count();
+    println!("{pending} pending migrations");
+    let modified = migrations.iter().filter(|m| m.modified).count();
+    if modified > 0 {
+        return Err(format!("{modified} applied migrations were modified").into());
+    }
+    Ok(())
+}
+
+fn export(output_dir: &Path) -> Result<(), Error> {
+    let conn = db::get_connection()?;
+    let items: Vec<JsonItem> = db::item::find_all(&conn)?
+        .into_iter()
+        .map(|item| JsonItem {
+            name: item.name,
+            wiki_url: item.wiki_url,
+            tags: item.tags,
+            effects_description: item.effects_description,
+        })
+        .collect();
+    let abilities: Vec<AbbreviatedAbility> = db::ability::find_all(&conn)?
+        .into_iter()
+        .map(AbbreviatedAbility::from)
+        .collect();
+    write_json(&output_dir.join("items.json"), &items)?;
+    write_json(&output_dir.join("abilities.json"), &abilities)?;
+    println!(
+        "Exported {} items and {} abilities to {}",
+        items.len(),
+        abilities.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|err| format!("Failed to serialize {}: {err:?}", path.display()))?;
+    std::fs::write(path, json)
+        .map_err(|err| format!("Failed to write {}: {err:?}", path.display()).into())
+}
+
+fn validate(path: &str) -> Result<(), Error> {
+    let invalid = validate_file(path)?;
+    if invalid.is_empty() {
+        println!("{path} is valid");
+        return Ok(());
+    }
+    for record in &invalid {
+        println!("{record}");
+    }
+    Err(format!("{path} has {} schema violations", invalid.len()).into())
+}
+
+fn reset_db(yes: bool) -> Result<(), Error> {
+    let path = &CONFIG.db_path;
+    if !yes {
+        print!("Delete the database at {path}? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Kept the database");
+            return Ok(());
+        }
+    }
+    std::fs::remove_file(path)
+        .map_err(|err| format!("Failed to remove the database at {path}: {err}"))?;
+    println!("Removed the database at {path}");
+    Ok(())
+}
+
+fn users(command: UsersCommand) -> Result<(), Error> {
+    let conn = db::get_connection()?;
+    match command {
+        UsersCommand::Add { email, role } => {
+            db::user::insert(&email, &role, &conn)?;
+            println!("Added {email} as {}", role.as_str());
+        }
+        UsersCommand::SetRole { email, role } => {
+            db::user::set_role(&email, &role, &conn)?;
+            println!("{email} is now {}", role.as_str());
+        }
+        UsersCommand::List => {
+            for user in db::user::find_all(&conn)? {
+                println!("{}\t{}", user.email, user.role.as_str());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the configuration, without the secret, and fails if any of the paths is missing.
+fn check_config() -> Result<(), Error> {
+    let config = serde_json::to_value(&*CONFIG)
+        .map_err(|err| format!("Failed to serialize the configuration: {err:?}"))?;
+    if let Some(fields) = config.as_object() {
+        for (field, value) in fields {
+            if field == "auth_secret" {
+                println!("{field} = <redacted>");
+            } else {
+                println!("{field} = {value}");
+            }
+        }
+    }
+
+    // The third value tells whether the binary has an embedded copy to fall back to
+    let mut paths = vec![
+        (
+            "abilities_path",
+            CONFIG.abilities_path.as_str(),
+            Resource::Abilities.embedded().is_some(),
+        ),
+        (
+            "items_path",
+            &CONFIG.items_path,
+            Resource::Items.embedded().is_some(),
+        ),
+        (
+            "db_migrations",
+            &CONFIG.db_migrations,
+            !resources::embedded_migrations().is_empty(),
+        ),
+        (
+            "tags_path",
+            &CONFIG.tags_path,
+            Resource::Tags.embedded().is_some(),
+        ),
+        (
+            "items_schema_path",
+            &CONFIG.items_schema_path,
+            Resource::ItemsSchema.embedded().is_some(),
+        ),
+        (
+            "abilities_schema_path",
+            &CONFIG.abilities_schema_path,
+            Resource::AbilitiesSchema.embedded().is_some(),
+        ),
+    ];
+    if let Some(dir) = &CONFIG.game_data_dir {
+        paths.push(("game_data_dir", dir, false));
+    }
+    if let Some(path) = &CONFIG.tls_cert_path {
+        paths.push(("tls_cert_path", path, false));
+    }
+    if let Some(path) = &CONFIG.tls_key_path {
+        paths.push(("tls_key_path", path, false));
+    }
+    if let Some(path) = &CONFIG.oidc_jwks_path {
+        paths.push(("oidc_jwks_path", path, false));
+    }
+    let db_dir = Path::new(&CONFIG.db_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut problems = Vec::new();
+    for (field, path, embedded) in paths {
+        if Path::new(path).exists() {
+            continue;
+        }
+        if embedded {
+            println!("{field}: {path} does not exist, using the embedded copy");
+        } else {
+            problems.push(format!("{field}: {path} does not exist"));
+        }
+    }
+    if !db_dir.is_dir() {
+        problems.push(format!(
+            "db_path: the directory {} does not exist",
+            db_dir.display()
+        ));
+    }
+    if CONFIG.tls_cert_path.is_some() != CONFIG.tls_key_path.is_some() {
+        problems.push("tls_cert_path and tls_key_path must be set together".to_string());
+    }
+    if CONFIG.auth_secret.is_empty() {
+        problems.push("auth_secret is empty".to_string());
+    }
+    if CONFIG.oidc_audience.is_none() {
+        problems.push("oidc_audience is not set, so nobody can log in".to_string());
+    }
+
+    if problems.is_empty() {
+        println!("The configuration is valid");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Err(format!("The configuration has {} problems", problems.len()).into())
+}
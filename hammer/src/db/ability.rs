@@ -1,31 +1,61 @@
 use crate::{
-    error::Error,
-    models::{AbbreviatedAbility, PersistedAbbreviatedAbility},
+    db::changes::Since,
+    error::{Error, ErrorType},
+    models::{
+        AbbreviatedAbility, EntityTag, IndexedEntityType, PersistedAbbreviatedAbility, TagSource,
+        TrashedEntity,
+    },
+    tag_index,
 };
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension, Transaction};
+
+/// Selects the abilities together with their tags as a JSON array, so loading any number of
+/// abilities takes a single query. Callers add the `WHERE` clause and then `GROUP_BY_ID`.
+const SELECT_WITH_TAGS: &str = "SELECT a.id, a.slug, a.name, a.url, a.verified_at IS NOT NULL, \
+    json_group_array(t.tag_name) FILTER (WHERE t.tag_name IS NOT NULL), \
+    strftime('%Y-%m-%dT%H:%M:%SZ', a.created_at), strftime('%Y-%m-%dT%H:%M:%SZ', a.updated_at) \
+    FROM abilities a LEFT JOIN abilities_tags t ON t.ability_id = a.id";
+const GROUP_BY_ID: &str = "GROUP BY a.id ORDER BY a.id";
 
 pub(crate) fn find_all(conn: &Connection) -> Result<Vec<PersistedAbbreviatedAbility>, Error> {
-    let mut stmt = conn.prepare("SELECT * FROM abilities")?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE a.deleted_at IS NULL {GROUP_BY_ID}"
+    ))?;
     let mut rows = stmt.query([])?;
     let mut abilities = Vec::new();
     while let Some(row) = rows.next()? {
-        abilities.push(from_row(row, conn)?);
+        abilities.push(from_row(row)?);
     }
     Ok(abilities)
 }
 
-fn from_row(row: &rusqlite::Row, conn: &Connection) -> Result<PersistedAbbreviatedAbility, Error> {
+/// Finds the live abilities created or changed after `since`, or all of them if it is `None`.
+pub(crate) fn find_updated_since(
+    since: Option<&Since>,
+    conn: &Connection,
+) -> Result<Vec<PersistedAbbreviatedAbility>, Error> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE a.deleted_at IS NULL \
+        AND (?1 IS NULL OR a.updated_at >= ?1) AND (?2 IS NULL OR a.change_seq > ?2) {GROUP_BY_ID}"
+    ))?;
+    let (timestamp, seq) = Since::bounds(since);
+    let mut rows = stmt.query(rusqlite::params![timestamp, seq])?;
+    let mut abilities = Vec::new();
+    while let Some(row) = rows.next()? {
+        abilities.push(from_row(row)?);
+    }
+    Ok(abilities)
+}
+
+fn from_row(row: &rusqlite::Row) -> Result<PersistedAbbreviatedAbility, Error> {
     let id = row.get(0)?;
     let slug = row.get(1)?;
     let name = row.get(2)?;
     let wiki_url = row.get(3)?;
-
-    let mut stmt = conn.prepare("SELECT tag_name FROM abilities_tags WHERE ability_id=?1")?;
-    let mut tags = Vec::new();
-    let mut rows = stmt.query([id])?;
-    while let Some(row) = rows.next()? {
-        tags.push(row.get(0)?);
-    }
+    let verified = row.get(4)?;
+    let tags = super::parse_tags(&row.get::<_, String>(5)?)?;
+    let created_at = row.get(6)?;
+    let updated_at = row.get(7)?;
 
     Ok(PersistedAbbreviatedAbility {
         id,
@@ -33,15 +63,18 @@ fn from_row(row: &rusqlite::Row, conn: &Connection) -> Result<PersistedAbbreviat
         name,
         tags,
         wiki_url,
+        verified,
+        created_at,
+        updated_at,
     })
 }
 
-pub(crate) fn insert_abbreviated_ability(ability: &AbbreviatedAbility) -> Result<(), Error> {
-    let mut conn =
-        crate::db::get_connection().map_err(|e| format!("Failed to get DB connection: {e:?}"))?;
-    let tx = conn.transaction().map_err(|e| {
-        format!("Failed to start transaction for inserting abbreviated ability: {e:?}")
-    })?;
+/// Inserts the ability as part of a transaction owned by the caller.
+pub(crate) fn insert_abbreviated_ability(
+    ability: &AbbreviatedAbility,
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<(), Error> {
     let mut stmt = tx.prepare("INSERT INTO abilities (name, slug, url) values (?1, ?2, ?3)")?;
     let id = stmt
         .insert(rusqlite::params![
@@ -49,143 +82,284 @@ pub(crate) fn insert_abbreviated_ability(ability: &AbbreviatedAbility) -> Result
             &ability.slug,
             &ability.wiki_url
         ])
-        .inspect_err(|err| tracing::warn!("Failed to insert ability into table. {err:?}"))?;
+        .inspect_err(|err| tracing::warn!("Failed to insert ability into table. {err:?}"))
+        .map_err(|err| unique_error(err, &ability.name, &ability.slug, tx))?;
     drop(stmt);
+    replace_tags(id, &ability.tags, source, tx)
+        .inspect_err(|err| tracing::warn!("Failed to insert tags of {}. {err:?}", ability.slug))?;
+    Ok(())
+}
+
+/// The error of a write that failed on a constraint. Trashed abilities keep their unique name
+/// and slug, so reusing them fails until the ability is restored or purged.
+fn unique_error(err: rusqlite::Error, name: &str, slug: &str, conn: &Connection) -> Error {
+    if err.sqlite_error_code() == Some(rusqlite::ErrorCode::ConstraintViolation) {
+        let trashed = conn
+            .prepare_cached(
+                "SELECT slug FROM abilities WHERE (name = ?1 OR slug = ?2) AND deleted_at IS NOT NULL",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_row([name, slug], |row| row.get::<_, String>(0))
+                    .optional()
+            });
+        if let Ok(Some(trashed)) = trashed {
+            return Error(
+                format!("The ability {trashed} is in the trash, restore or purge it first"),
+                ErrorType::Conflict,
+            );
+        }
+    }
+    err.into()
+}
+
+fn find_id_by_slug(slug: &str, conn: &Connection) -> Result<i64, Error> {
+    let mut stmt =
+        conn.prepare_cached("SELECT id FROM abilities WHERE slug=?1 AND deleted_at IS NULL")?;
+    stmt.query_row([slug], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| Error(format!("Ability {slug} not found"), ErrorType::NotFound))
+}
+
+/// Sets the tags of the ability. Tags the ability already has keep their original source.
+fn replace_tags(
+    id: i64,
+    new_tags: &[String],
+    source: &TagSource,
+    conn: &Connection,
+) -> Result<(), Error> {
     let mut stmt =
-        tx.prepare("INSERT INTO abilities_tags (ability_id, tag_name) VALUES (?1, ?2)")?;
-    for tag in &ability.tags {
-        stmt.insert(rusqlite::params![id, tag])
-            .inspect_err(|err| tracing::warn!("Failed to insert abilities tag {tag}. {err:?}"))?;
+        conn.prepare_cached("SELECT tag_name FROM abilities_tags WHERE ability_id=?1")?;
+    let current_tags = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    drop(stmt);
+
+    let mut stmt =
+        conn.prepare_cached("DELETE FROM abilities_tags WHERE ability_id=?1 AND tag_name=?2")?;
+    for tag in current_tags.iter().filter(|tag| !new_tags.contains(tag)) {
+        stmt.execute(rusqlite::params![id, tag])?;
     }
     drop(stmt);
-    tx.commit()?;
-    Ok(())
+
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO abilities_tags (ability_id, tag_name, source, source_ref) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let mut inserted: Vec<&String> = Vec::new();
+    for tag in new_tags {
+        if current_tags.contains(tag) || inserted.contains(&tag) {
+            continue;
+        }
+        stmt.execute(rusqlite::params![
+            id,
+            tag,
+            source.kind(),
+            source.reference()
+        ])?;
+        inserted.push(tag);
+    }
+    drop(stmt);
+    tag_index::refresh(IndexedEntityType::Ability, id, conn)
 }
 
-fn find_ability_tags_by_id(id: i64, conn: &Connection) -> Result<Vec<String>, Error> {
-    let mut stmt = conn.prepare("SELECT tag_name FROM abilities_tags WHERE ability_id=?1")?;
-    let mut rows = stmt.query([id])?;
-    let mut tags = Vec::default();
-    while let Some(row) = rows.next()? {
-        tags.push(row.get(0)?);
+/// Returns the fields of the ability that editors locked against imports.
+pub(crate) fn find_locks(slug: &str, conn: &Connection) -> Result<Vec<String>, Error> {
+    let id = find_id_by_slug(slug, conn)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT field FROM abilities_field_locks WHERE ability_id=?1 ORDER BY field",
+    )?;
+    let locks = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(locks)
+}
+
+/// Replaces the locked fields of the ability as part of a transaction owned by the caller.
+pub(crate) fn set_locks(
+    slug: &str,
+    fields: &[String],
+    locked_by: &str,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let id = find_id_by_slug(slug, tx)?;
+    let mut stmt =
+        tx.prepare_cached("SELECT field FROM abilities_field_locks WHERE ability_id=?1")?;
+    let current_locks = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    drop(stmt);
+    let mut stmt =
+        tx.prepare_cached("DELETE FROM abilities_field_locks WHERE ability_id=?1 AND field=?2")?;
+    for field in current_locks.iter().filter(|field| !fields.contains(field)) {
+        stmt.execute(rusqlite::params![id, field])?;
+    }
+    drop(stmt);
+    let mut stmt = tx.prepare_cached(
+        "INSERT OR IGNORE INTO abilities_field_locks (ability_id, field, locked_by) VALUES (?1, ?2, ?3)",
+    )?;
+    for field in fields {
+        stmt.execute(rusqlite::params![id, field, locked_by])?;
     }
+    drop(stmt);
+    Ok(())
+}
+
+pub(crate) fn find_tag_sources(slug: &str, conn: &Connection) -> Result<Vec<EntityTag>, Error> {
+    let id = find_id_by_slug(slug, conn)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT tag_name, source, source_ref FROM abilities_tags WHERE ability_id=?1 ORDER BY tag_name",
+    )?;
+    let tags = stmt
+        .query_map([id], |row| {
+            Ok(EntityTag {
+                name: row.get(0)?,
+                source: row.get(1)?,
+                source_ref: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(tags)
 }
 
-pub(crate) fn find_abbreviated_abilities_by_ids(
-    ids: &[i64],
+/// Marks the ability as verified by the editor, or clears the flag if `verified_by` is `None`.
+/// Returns `false` if there is no ability with the given slug.
+pub(crate) fn set_verified(
+    slug: &str,
+    verified_by: Option<&str>,
     conn: &Connection,
-) -> Result<Vec<PersistedAbbreviatedAbility>, Error> {
-    if ids.is_empty() {
-        return Ok(Vec::default());
-    }
+) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE abilities SET verified_at = CASE WHEN ?1 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, verified_by = ?1 WHERE slug = ?2 AND deleted_at IS NULL RETURNING id",
+    )?;
+    let id = stmt
+        .query_row(rusqlite::params![verified_by, slug], |row| row.get(0))
+        .optional()?;
+    refresh_index(id, conn)
+}
 
-    let placeholder = ids
-        .iter()
-        .map(|id| format!("{id}"))
-        .collect::<Vec<_>>()
-        .join(",");
-    let mut stmt = conn.prepare(&format!(
-        "SELECT id, name, slug, url FROM abilities WHERE id IN ({placeholder})"
-    ))?;
+/// Finds the slug of the ability reconciled with the game data object.
+pub(crate) fn find_slug_by_game_data_id(
+    game_data_id: &str,
+    conn: &Connection,
+) -> Result<Option<String>, Error> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT slug FROM abilities WHERE game_data_id = ?1 AND deleted_at IS NULL",
+    )?;
+    Ok(stmt
+        .query_row([game_data_id], |row| row.get(0))
+        .optional()?)
+}
+
+/// Records the game data object the ability is reconciled with, so later imports of the game
+/// data find it by that ID even if its name, and so its slug, changed.
+pub(crate) fn set_game_data_id(
+    slug: &str,
+    game_data_id: &str,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("UPDATE abilities SET game_data_id = ?2 WHERE slug = ?1")?;
+    stmt.execute([slug, game_data_id])?;
+    Ok(())
+}
+
+/// Moves the ability to the trash.
+/// Returns `false` if there is no ability with the given slug outside of the trash.
+pub(crate) fn delete(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE abilities SET deleted_at = CURRENT_TIMESTAMP WHERE slug = ?1 AND deleted_at IS NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
+}
+
+pub(crate) fn find_deleted(conn: &Connection) -> Result<Vec<TrashedEntity>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT name, slug, deleted_at FROM abilities WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )?;
     let mut rows = stmt.query([])?;
-    let mut abilities = Vec::with_capacity(ids.len());
+    let mut abilities = Vec::new();
     while let Some(row) = rows.next()? {
-        let id: i64 = row.get(0)?;
-        let tags = find_ability_tags_by_id(id, conn)?;
-        abilities.push(PersistedAbbreviatedAbility {
-            id,
-            name: row.get(1).map_err(|_| "Failed to get name".to_string())?,
-            slug: row.get(2).map_err(|_| "Failed to get slug".to_string())?,
-            tags,
-            wiki_url: row
-                .get(3)
-                .map_err(|_| "Failed to get wiki_url".to_string())?,
+        abilities.push(TrashedEntity {
+            name: row.get(0)?,
+            slug: row.get(1)?,
+            deleted_at: row.get(2)?,
+            entity_type: IndexedEntityType::Ability,
         });
     }
-    tracing::trace!("Done with returning abilities by id");
     Ok(abilities)
 }
 
-pub(crate) fn delete_abbreviated_ability_by_slug(slug: &str) -> Result<(), String> {
-    let conn =
-        crate::db::get_connection().map_err(|e| format!("Failed to get DB connection: {e:?}"))?;
-    let mut stmt = conn
-        .prepare("DELETE from abilities where slug = ?1")
-        .map_err(|e| format!("Failed to prepare the delete statement: {e:?}"))?;
-    stmt.execute([slug])
-        .map_err(|e| format!("Failed to delete the ability: {e:?}"))?;
-    Ok(())
+/// Returns `false` if there is no deleted ability with the given slug.
+pub(crate) fn restore(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE abilities SET deleted_at = NULL WHERE slug = ?1 AND deleted_at IS NOT NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
 }
 
-pub(crate) fn update_abbreviated_ability_by_slug(
+/// Permanently removes a deleted ability together with its tags.
+/// Returns `false` if there is no deleted ability with the given slug.
+pub(crate) fn purge(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "DELETE FROM abilities WHERE slug = ?1 AND deleted_at IS NOT NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
+}
+
+/// Refreshes the ability in the tag index if the write found it.
+/// Returns whether there was such an ability.
+fn refresh_index(id: Option<i64>, conn: &Connection) -> Result<bool, Error> {
+    match id {
+        Some(id) => tag_index::refresh(IndexedEntityType::Ability, id, conn).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Updates the ability as part of a transaction owned by the caller.
+pub(crate) fn update_abbreviated_ability_in_transaction(
     slug: &str,
     ability: AbbreviatedAbility,
+    source: &TagSource,
+    tx: &Transaction,
 ) -> Result<(), Error> {
-    let mut conn = crate::db::get_connection()?;
-    let tx = conn.transaction()?;
+    let id = find_id_by_slug(slug, tx)?;
 
     let mut stmt = tx
-        .prepare("UPDATE abilities SET name = ?1, url = ?2, slug = ?3 WHERE slug = ?4")
+        .prepare("UPDATE abilities SET name = ?1, url = ?2, slug = ?3 WHERE id = ?4")
         .map_err(|e| format!("Failed to prepare the update statement: {e:?}"))?;
     let _ = stmt
         .execute(rusqlite::params![
             &ability.name,
             &ability.wiki_url,
-            slug::slugify(&ability.name),
-            slug
+            &ability.slug,
+            id
         ])
-        .map_err(|e| format!("Failed to update the ability: {e:?}"))?;
-    drop(stmt);
-    // Ensure previous tags are removed
-    let mut stmt = tx
-        .prepare("DELETE FROM abilities_tags WHERE ability_id=(SELECT id FROM abilities WHERE slug = ?1)")
-        .map_err(|e| format!("Failed to prepare the delete tags statement: {e:?}"))?;
-    let _ = stmt
-        .execute([slug])
-        .map_err(|e| format!("Failed to delete tags: {e:?}"))?;
+        .map_err(|err| unique_error(err, &ability.name, &ability.slug, tx))?;
     drop(stmt);
-    // Add new tags
-    let mut stmt = tx.prepare("INSERT INTO abilities_tags (ability_id, tag_name) VALUES ((SELECT id FROM abilities WHERE slug=?1), ?2)")
-        .map_err(|e| format!("Failed to prepare the insert tags statement: {e:?}"))?;
-    for tag in ability.tags {
-        stmt.execute(rusqlite::params![slug, tag])
-            .map_err(|e| format!("Failed to insert tag: {e:?}"))?;
-    }
-
-    drop(stmt);
-    tx.commit()?;
+    replace_tags(id, &ability.tags, source, tx)
+        .map_err(|e| format!("Failed to update tags: {e:?}"))?;
     Ok(())
 }
 
-pub(crate) fn update_tags_by_slug(
+/// Sets the tags of the ability as part of a transaction owned by the caller.
+pub(crate) fn update_tags_in_transaction(
     slug: &str,
-    new_tags: Vec<String>,
-    conn: &mut Connection,
-) -> Result<Vec<String>, Error> {
-    let tx = conn.transaction()?;
-
-    let mut stmt = tx.prepare_cached(
-        "DELETE FROM abilities_tags WHERE ability_id=(SELECT id FROM abilities WHERE slug=?1)",
-    )?;
-    stmt.execute([slug])?;
-    drop(stmt);
-
-    let mut stmt = tx.prepare_cached("INSERT INTO abilities_tags (ability_id, tag_name) VALUES ((SELECT id FROM abilities WHERE slug=?1), ?2)")?;
-    for tag in &new_tags {
-        stmt.execute([slug, tag])?;
-    }
-    drop(stmt);
-    tx.commit()?;
-
-    Ok(new_tags)
+    new_tags: &[String],
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let id = find_id_by_slug(slug, tx)?;
+    replace_tags(id, new_tags, source, tx)
 }
 
 pub(crate) fn find_by_slug(
     slug: &str,
     conn: &Connection,
 ) -> Result<Option<PersistedAbbreviatedAbility>, Error> {
-    let mut stmt = conn.prepare_cached("SELECT * FROM abilities WHERE slug=?1")?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE a.slug=?1 AND a.deleted_at IS NULL {GROUP_BY_ID}"
+    ))?;
     let mut row = stmt.query([slug])?;
-    Ok(row.next()?.and_then(|row| from_row(row, conn).ok()))
+    Ok(row.next()?.and_then(|row| from_row(row).ok()))
 }
@@ -0,0 +1,208 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::db::{ability, item};
+use crate::error::Error;
+use crate::models::{Changes, DeletedEntity};
+
+/// Selects the slugs deleted since the bounds `?1` and `?2` of [`Since::bounds`], or all of
+/// them if both are NULL: the entities in the trash and the tombstones of the purged and
+/// renamed ones.
+const SELECT_DELETED: &str = "SELECT 'ability', slug, deleted_at FROM abilities \
+    WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at >= ?1) AND (?2 IS NULL OR change_seq > ?2) \
+    UNION ALL SELECT 'item', slug, deleted_at FROM items \
+    WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at >= ?1) AND (?2 IS NULL OR change_seq > ?2) \
+    UNION ALL SELECT entity_type, slug, deleted_at FROM tombstones \
+    WHERE (?1 IS NULL OR deleted_at >= ?1) AND (?2 IS NULL OR change_seq > ?2) \
+    ORDER BY 3, 1, 2";
+
+/// The last value of the change sequence that every write to an entity or a tombstone takes
+/// the next value of.
+const SELECT_SEQUENCE: &str = "SELECT value FROM change_sequence WHERE id = 1";
+
+/// Converts a timestamp to the UTC format of `CURRENT_TIMESTAMP` that the tables store.
+/// SQLite accepts RFC 3339 with a `Z` or an offset, with or without the `T`.
+const NORMALIZE: &str = "SELECT datetime(?1)";
+
+/// Where the changes start from.
+#[derive(Debug)]
+pub(crate) enum Since {
+    /// The `next` of a previous response: a value of the change sequence.
+    Token(i64),
+    /// A UTC time in the format of `CURRENT_TIMESTAMP`.
+    Timestamp(String),
+}
+
+impl Since {
+    /// Parses the `next` of a previous response, or an RFC 3339 timestamp.
+    fn parse(since: &str, conn: &Connection) -> Result<Self, Error> {
+        match since.parse() {
+            Ok(token) => Ok(Self::Token(token)),
+            Err(_) => normalize(since, conn).map(Self::Timestamp),
+        }
+    }
+
+    /// The lower bounds of `updated_at` and of `change_seq`, for queries that take them as `?1`
+    /// and `?2` and skip a bound that is NULL.
+    pub(crate) fn bounds(since: Option<&Self>) -> (Option<&str>, Option<i64>) {
+        match since {
+            Some(Self::Token(token)) => (None, Some(*token)),
+            Some(Self::Timestamp(timestamp)) => (Some(timestamp), None),
+            None => (None, None),
+        }
+    }
+}
+
+/// Finds the entities created, changed or deleted since `since`, or everything if it is
+/// `None`.
+///
+/// `next` is a value of the change sequence, not a time. Writers take the values in the order
+/// they commit, so a client passing `next` back gets exactly the changes committed after the
+/// response, including those of transactions that were still running when it was made. Since a
+/// timestamp is the time of the statement that made the change, the bound is inclusive with a
+/// precision of a second, and a transaction that committed later can still be missed, so a
+/// timestamp only suits the first request of a client.
+pub(crate) fn find_since(since: Option<&str>, conn: &mut Connection) -> Result<Changes, Error> {
+    let since = match since {
+        Some(since) => Some(Since::parse(since, conn)?),
+        None => None,
+    };
+    // One read transaction, so all of the changes and `next` come from the same snapshot
+    let tx = conn.transaction()?;
+    let next: i64 = tx.query_row(SELECT_SEQUENCE, [], |row| row.get(0))?;
+    let items = item::find_updated_since(since.as_ref(), &tx)?;
+    let abilities = ability::find_updated_since(since.as_ref(), &tx)?;
+
+    let mut stmt = tx.prepare_cached(SELECT_DELETED)?;
+    let (timestamp, seq) = Since::bounds(since.as_ref());
+    let mut rows = stmt.query(rusqlite::params![timestamp, seq])?;
+    let mut deleted = Vec::new();
+    while let Some(row) = rows.next()? {
+        let deleted_at: String = row.get(2)?;
+        deleted.push(DeletedEntity {
+            entity_type: row.get::<_, String>(0)?.parse()?,
+            slug: row.get(1)?,
+            deleted_at: to_rfc3339(&deleted_at),
+        });
+    }
+    drop(rows);
+    drop(stmt);
+    tx.commit()?;
+
+    Ok(Changes {
+        next: next.to_string(),
+        items: items.into_iter().map(Into::into).collect(),
+        abilities: abilities.into_iter().map(Into::into).collect(),
+        deleted,
+    })
+}
+
+fn normalize(since: &str, conn: &Connection) -> Result<String, Error> {
+    conn.query_row(NORMALIZE, [since], |row| row.get::<_, Option<String>>(0))
+        .optional()?
+        .flatten()
+        .ok_or_else(|| format!("{since} is not a valid timestamp").into())
+}
+
+/// `CURRENT_TIMESTAMP` is `YYYY-MM-DD HH:MM:SS` in UTC.
+fn to_rfc3339(timestamp: &str) -> String {
+    format!("{}Z", timestamp.replacen(' ', "T", 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{get_test_connection, indexed};
+    use crate::models::IndexedEntityType;
+
+    fn slugs<T>(entities: &[T], slug: impl Fn(&T) -> &str) -> Vec<&str> {
+        entities.iter().map(slug).collect()
+    }
+
+    #[test]
+    fn test_changes_since_a_token_or_a_timestamp() {
+        let mut conn = get_test_connection();
+        indexed::insert_test_entities(&mut conn);
+        // Everything so far happened a day ago. The timestamp triggers would overwrite it.
+        let triggers = rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER;
+        conn.set_db_config(triggers, false).unwrap();
+        for table in ["abilities", "items"] {
+            conn.execute(
+                &format!(
+                    "UPDATE {table} SET created_at = datetime('now', '-1 day'), \
+                    updated_at = datetime('now', '-1 day')"
+                ),
+                [],
+            )
+            .unwrap();
+        }
+        conn.set_db_config(triggers, true).unwrap();
+        let all = find_since(None, &mut conn).unwrap();
+        assert_eq!(
+            slugs(&all.abilities, |a| &a.entity.slug),
+            ["fireball", "frostfire"]
+        );
+        assert_eq!(slugs(&all.items, |i| &i.entity.slug), ["torch"]);
+        let hour_ago: String = conn
+            .query_row(
+                "SELECT strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-1 hour')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+
+        make_changes(&conn);
+        let changes = find_since(Some(&all.next), &mut conn).unwrap();
+        assert_eq!(slugs(&changes.abilities, |a| &a.entity.slug), ["frostfire"]);
+        assert!(changes.items.is_empty());
+        let deleted: Vec<(IndexedEntityType, &str)> = changes
+            .deleted
+            .iter()
+            .map(|entity| (entity.entity_type, entity.slug.as_str()))
+            .collect();
+        assert!(deleted.contains(&(IndexedEntityType::Ability, "fireball")));
+        assert!(deleted.contains(&(IndexedEntityType::Item, "torch")));
+        assert_eq!(deleted.len(), 2);
+        let again = find_since(Some(&changes.next), &mut conn).unwrap();
+        assert!(again.abilities.is_empty() && again.items.is_empty() && again.deleted.is_empty());
+
+        for timestamp in [hour_ago.clone(), hour_ago.replace('Z', "+00:00")] {
+            let changes_since_then = find_since(Some(&timestamp), &mut conn).unwrap();
+            assert_eq!(changes_since_then.deleted, changes.deleted);
+        }
+        assert!(find_since(Some("yesterday"), &mut conn).is_err());
+    }
+
+    #[test]
+    fn test_changes_committed_after_a_response_are_not_missed() {
+        let path = std::env::temp_dir().join(format!("hammer-changes-{}.db3", std::process::id()));
+        let mut writer = Connection::open(&path).unwrap();
+        crate::db::synchronize_db(&writer).unwrap();
+        indexed::insert_test_entities(&mut writer);
+        let mut reader = Connection::open(&path).unwrap();
+
+        // An import that changed an entity before the response, and commits after it
+        writer.execute_batch("BEGIN IMMEDIATE").unwrap();
+        make_changes(&writer);
+        let before_commit = find_since(None, &mut reader).unwrap();
+        assert!(before_commit.deleted.is_empty());
+        writer.execute_batch("COMMIT").unwrap();
+
+        let changes = find_since(Some(&before_commit.next), &mut reader).unwrap();
+        assert_eq!(slugs(&changes.abilities, |a| &a.entity.slug), ["frostfire"]);
+        assert_eq!(changes.deleted.len(), 2);
+        drop((writer, reader));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Edits `frostfire`, moves `fireball` to the trash and purges `torch`.
+    fn make_changes(conn: &Connection) {
+        conn.execute(
+            "UPDATE abilities SET name = 'Frostfire' WHERE slug = 'frostfire'",
+            [],
+        )
+        .unwrap();
+        ability::delete("fireball", conn).unwrap();
+        item::delete("torch", conn).unwrap();
+        item::purge("torch", conn).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use crate::error::Error;
+use crate::models::{ImportRun, ImportRunChange, SyncCounts};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+const SELECT_RUNS: &str = "SELECT id, items_path, items_hash, abilities_path, abilities_hash, items_created, items_updated, items_unchanged, items_removed, abilities_created, abilities_updated, abilities_unchanged, abilities_removed, conflicts, started_at, finished_at, rolled_back_at FROM import_runs";
+
+fn from_row(row: &Row) -> Result<ImportRun, Error> {
+    Ok(ImportRun {
+        id: row.get(0)?,
+        items_path: row.get(1)?,
+        items_hash: row.get(2)?,
+        abilities_path: row.get(3)?,
+        abilities_hash: row.get(4)?,
+        items: SyncCounts {
+            created: row.get(5)?,
+            updated: row.get(6)?,
+            unchanged: row.get(7)?,
+            removed: row.get(8)?,
+        },
+        abilities: SyncCounts {
+            created: row.get(9)?,
+            updated: row.get(10)?,
+            unchanged: row.get(11)?,
+            removed: row.get(12)?,
+        },
+        conflicts: row.get(13)?,
+        started_at: row.get(14)?,
+        finished_at: row.get(15)?,
+        rolled_back_at: row.get(16)?,
+    })
+}
+
+/// Records the start of an import run. Paths are paired with the hashes of their contents.
+pub(crate) fn insert(
+    items: (&str, &str),
+    abilities: (&str, &str),
+    conn: &Connection,
+) -> Result<i64, Error> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO import_runs (items_path, items_hash, abilities_path, abilities_hash, started_at) VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)",
+    )?;
+    Ok(stmt.insert(rusqlite::params![
+        items.0,
+        items.1,
+        abilities.0,
+        abilities.1
+    ])?)
+}
+
+pub(crate) fn finish(
+    id: i64,
+    items: &SyncCounts,
+    abilities: &SyncCounts,
+    conflicts: usize,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE import_runs SET items_created=?1, items_updated=?2, items_unchanged=?3, items_removed=?4, abilities_created=?5, abilities_updated=?6, abilities_unchanged=?7, abilities_removed=?8, conflicts=?9, finished_at=CURRENT_TIMESTAMP WHERE id=?10",
+    )?;
+    stmt.execute(rusqlite::params![
+        items.created,
+        items.updated,
+        items.unchanged,
+        items.removed,
+        abilities.created,
+        abilities.updated,
+        abilities.unchanged,
+        abilities.removed,
+        conflicts,
+        id
+    ])?;
+    Ok(())
+}
+
+pub(crate) fn insert_change(
+    run_id: i64,
+    change: &ImportRunChange,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO import_run_changes (run_id, entity_type, slug, action, before, after) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    stmt.execute(rusqlite::params![
+        run_id,
+        change.entity_type.as_str(),
+        &change.slug,
+        change.action.as_str(),
+        &change.before,
+        &change.after
+    ])?;
+    Ok(())
+}
+
+pub(crate) fn find_all(conn: &Connection) -> Result<Vec<ImportRun>, Error> {
+    let mut stmt = conn.prepare_cached(&format!("{SELECT_RUNS} ORDER BY id DESC"))?;
+    let mut rows = stmt.query([])?;
+    let mut runs = Vec::new();
+    while let Some(row) = rows.next()? {
+        runs.push(from_row(row)?);
+    }
+    Ok(runs)
+}
+
+pub(crate) fn find_by_id(id: i64, conn: &Connection) -> Result<Option<ImportRun>, Error> {
+    let mut stmt = conn.prepare_cached(&format!("{SELECT_RUNS} WHERE id=?1"))?;
+    stmt.query_row([id], |row| Ok(from_row(row)))
+        .optional()?
+        .transpose()
+}
+
+/// Returns the changes of the run, latest first.
+pub(crate) fn find_changes(run_id: i64, conn: &Connection) -> Result<Vec<ImportRunChange>, Error> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT entity_type, slug, action, before, after FROM import_run_changes WHERE run_id=?1 ORDER BY id DESC",
+    )?;
+    let mut rows = stmt.query([run_id])?;
+    let mut changes = Vec::new();
+    while let Some(row) = rows.next()? {
+        let entity_type: String = row.get(0)?;
+        let action: String = row.get(2)?;
+        changes.push(ImportRunChange {
+            entity_type: entity_type.parse()?,
+            slug: row.get(1)?,
+            action: action.parse()?,
+            before: row.get(3)?,
+            after: row.get(4)?,
+        });
+    }
+    Ok(changes)
+}
+
+pub(crate) fn mark_rolled_back(id: i64, conn: &Connection) -> Result<(), Error> {
+    let mut stmt =
+        conn.prepare_cached("UPDATE import_runs SET rolled_back_at=CURRENT_TIMESTAMP WHERE id=?1")?;
+    stmt.execute([id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChangeAction, IndexedEntityType};
+
+    #[test]
+    fn test_run_is_recorded_with_changes() {
+        let conn = crate::db::get_test_connection();
+        let id = insert(
+            ("items.json", "sha256:a"),
+            ("abilities.json", "sha256:b"),
+            &conn,
+        )
+        .unwrap();
+        let change = ImportRunChange {
+            entity_type: IndexedEntityType::Item,
+            slug: "drawn-in-spring".to_string(),
+            action: ChangeAction::Created,
+            before: None,
+            after: Some("{}".to_string()),
+        };
+        insert_change(id, &change, &conn).unwrap();
+        let counts = SyncCounts {
+            created: 1,
+            ..Default::default()
+        };
+        finish(id, &counts, &SyncCounts::default(), 0, &conn).unwrap();
+
+        let run = find_by_id(id, &conn).unwrap().unwrap();
+        assert_eq!(run.items.created, 1);
+        assert!(run.finished_at.is_some());
+        assert!(run.rolled_back_at.is_none());
+        let changes = find_changes(id, &conn).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].action, ChangeAction::Created);
+
+        mark_rolled_back(id, &conn).unwrap();
+        assert!(
+            find_by_id(id, &conn)
+                .unwrap()
+                .unwrap()
+                .rolled_back_at
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use rusqlite::Connection;
+
+use crate::error::Error;
+use crate::models::{FilterParams, IndexedEntity, IndexedEntityType, SourceFilter};
+
+/// Selects the entities of one kind that match the tag filter, with all of their tags.
+/// `{table}`, `{tags_table}` and `{id_column}` name the kind, `{matches}` is the tag filter.
+///
+/// The parameters are bound the same way for any filter:
+/// - `?1` is a JSON array of the wanted tag names,
+/// - `?2` is the wanted verification status, or NULL for any,
+/// - `?3` is a JSON array of the accepted tag sources, or NULL for any,
+/// - `?4` is a JSON array of the excluded tag names.
+const SELECT_MATCHING: &str = "SELECT '{kind}', e.id, e.name, e.slug, e.{url_column}, \
+    e.verified_at IS NOT NULL, json_group_array(t.tag_name) FILTER (WHERE t.tag_name IS NOT NULL) \
+    FROM {table} e LEFT JOIN {tags_table} t ON t.{id_column} = e.id \
+    WHERE e.deleted_at IS NULL AND (?2 IS NULL OR (e.verified_at IS NOT NULL) = ?2) AND {matches} \
+    AND NOT EXISTS (SELECT 1 FROM {tags_table} x WHERE x.{id_column} = e.id \
+    AND x.tag_name IN (SELECT value FROM json_each(?4))) \
+    GROUP BY e.id";
+
+/// Entities with at least one of the wanted tags.
+const MATCHES_ANY: &str = "EXISTS (SELECT 1 FROM {tags_table} m WHERE m.{id_column} = e.id \
+    AND m.tag_name IN (SELECT value FROM json_each(?1)) \
+    AND (?3 IS NULL OR m.source IN (SELECT value FROM json_each(?3))))";
+
+/// Entities with all of the wanted tags.
+const MATCHES_ALL: &str = "e.id IN (SELECT m.{id_column} FROM {tags_table} m \
+    WHERE m.tag_name IN (SELECT value FROM json_each(?1)) \
+    AND (?3 IS NULL OR m.source IN (SELECT value FROM json_each(?3))) \
+    GROUP BY m.{id_column} \
+    HAVING COUNT(DISTINCT m.tag_name) = (SELECT COUNT(DISTINCT value) FROM json_each(?1)))";
+
+/// Selects the live entities of one kind, or only the one with the id `?1`, with their tags
+/// and the sources of the tags as a JSON array of `[tag, source]` pairs.
+const SELECT_ENTRIES: &str = "SELECT e.id, e.name, e.slug, e.{url_column}, \
+    e.verified_at IS NOT NULL, \
+    json_group_array(json_array(t.tag_name, t.source)) FILTER (WHERE t.tag_name IS NOT NULL) \
+    FROM {table} e LEFT JOIN {tags_table} t ON t.{id_column} = e.id \
+    WHERE e.deleted_at IS NULL AND (?1 IS NULL OR e.id = ?1) \
+    GROUP BY e.id";
+
+/// An entity as the in-memory tag index stores it.
+#[derive(Debug, Clone)]
+pub(crate) struct IndexEntry {
+    pub(crate) id: i64,
+    pub(crate) entity: IndexedEntity,
+    /// The source of each of the entity's tags, in the same order.
+    pub(crate) sources: Vec<String>,
+}
+
+/// Finds the abilities and then the items whose tags match the filter in a single query.
+/// The SQL only depends on the filter logic, so every filter reuses one of two cached statements.
+pub(crate) fn find(params: &FilterParams, conn: &Connection) -> Result<Vec<IndexedEntity>, Error> {
+    let matches = if params.match_all()? {
+        MATCHES_ALL
+    } else {
+        MATCHES_ANY
+    };
+    let abilities = format_sql(SELECT_MATCHING, matches, IndexedEntityType::Ability);
+    let items = format_sql(SELECT_MATCHING, matches, IndexedEntityType::Item);
+    let mut stmt = conn.prepare_cached(&format!("{abilities} UNION ALL {items} ORDER BY 1, 2"))?;
+
+    let tags = serde_json::to_string(&params.tags).map_err(|err| err.to_string())?;
+    let sources = params.source.map(|source| match source {
+        SourceFilter::Human => r#"["editor","proposal"]"#,
+        SourceFilter::Import => r#"["import"]"#,
+    });
+    let excluded = serde_json::to_string(&params.exclude_tags).map_err(|err| err.to_string())?;
+    let mut rows = stmt.query(rusqlite::params![tags, params.verified, sources, excluded])?;
+    let mut entities = Vec::new();
+    while let Some(row) = rows.next()? {
+        entities.push(IndexedEntity {
+            entity_type: row.get::<_, String>(0)?.parse()?,
+            name: row.get(2)?,
+            slug: row.get(3)?,
+            wiki_url: row.get(4)?,
+            verified: row.get(5)?,
+            tags: super::parse_tags(&row.get::<_, String>(6)?)?,
+        });
+    }
+    Ok(entities)
+}
+
+/// Finds the live entities of the given type, or only the one with the given id, together
+/// with the sources of their tags.
+pub(crate) fn find_entries(
+    entity_type: IndexedEntityType,
+    id: Option<i64>,
+    conn: &Connection,
+) -> Result<Vec<IndexEntry>, Error> {
+    let mut stmt = conn.prepare_cached(&format_sql(SELECT_ENTRIES, "", entity_type))?;
+    let mut rows = stmt.query([id])?;
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        let json: String = row.get(5)?;
+        let tags: Vec<(String, String)> = serde_json::from_str(&json)
+            .map_err(|err| format!("Failed to parse the tags {json}: {err}"))?;
+        let (tags, sources) = tags.into_iter().unzip();
+        entries.push(IndexEntry {
+            id: row.get(0)?,
+            entity: IndexedEntity {
+                name: row.get(1)?,
+                slug: row.get(2)?,
+                wiki_url: row.get(3)?,
+                tags,
+                verified: row.get(4)?,
+                entity_type,
+            },
+            sources,
+        });
+    }
+    Ok(entries)
+}
+
+/// Fills in the table and column names of the entity type.
+fn format_sql(sql: &str, matches: &str, entity_type: IndexedEntityType) -> String {
+    let (table, id_column, url_column) = match entity_type {
+        IndexedEntityType::Ability => ("abilities", "ability_id", "url"),
+        IndexedEntityType::Item => ("items", "item_id", "wiki_url"),
+    };
+    sql.replace("{matches}", matches)
+        .replace("{kind}", entity_type.as_str())
+        .replace("{table}", table)
+        .replace("{tags_table}", &format!("{table}_tags"))
+        .replace("{id_column}", id_column)
+        .replace("{url_column}", url_column)
+}
+
+/// Inserts the abilities `fireball` (burn, imported) and `frostfire` (burn and freeze, by an
+/// editor) and the verified item `torch` (burn and heal, imported).
+#[cfg(test)]
+pub(crate) fn insert_test_entities(conn: &mut Connection) {
+    use crate::db::{ability, item, tag};
+    use crate::models::{AbbreviatedAbility, Item, TagSource};
+
+    for name in ["burn", "freeze", "heal"] {
+        // The migrations may already have added some of the tags
+        let _ = tag::insert(name, "", conn);
+    }
+    let import = TagSource::Import("run".to_string());
+    let editor = TagSource::Editor("editor@example.com".to_string());
+    let tx = conn.transaction().unwrap();
+    let ability = |slug: &str, tags: &[&str]| AbbreviatedAbility {
+        name: slug.to_string(),
+        slug: slug.to_string(),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        wiki_url: String::new(),
+    };
+    ability::insert_abbreviated_ability(&ability("fireball", &["burn"]), &import, &tx).unwrap();
+    ability::insert_abbreviated_ability(&ability("frostfire", &["burn", "freeze"]), &editor, &tx)
+        .unwrap();
+    item::insert_in_transaction(
+        &Item {
+            name: "Torch".to_string(),
+            slug: "torch".to_string(),
+            wiki_url: String::new(),
+            tags: vec!["burn".to_string(), "heal".to_string()],
+            effects_description: String::new(),
+        },
+        &import,
+        &tx,
+    )
+    .unwrap();
+    tx.commit().unwrap();
+    item::set_verified("torch", Some("editor@example.com"), conn).unwrap();
+}
+
+/// Filters over the entities of [`insert_test_entities`] with the slugs they should find, for
+/// the tests of both the query and the in-memory index.
+#[cfg(test)]
+pub(crate) fn test_filters() -> Vec<(FilterParams, Vec<&'static str>)> {
+    let filter = |tags: &[&str],
+                  filter_logic: &str,
+                  verified: Option<bool>,
+                  source: Option<SourceFilter>,
+                  exclude_tags: &[&str]| FilterParams {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        filter_logic: filter_logic.to_string(),
+        verified,
+        source,
+        exclude_tags: exclude_tags.iter().map(|tag| tag.to_string()).collect(),
+    };
+    vec![
+        (
+            filter(&["freeze", "heal"], "or", None, None, &[]),
+            vec!["frostfire", "torch"],
+        ),
+        (
+            filter(&["burn", "freeze", "burn"], "and", None, None, &[]),
+            vec!["frostfire"],
+        ),
+        (
+            filter(&["burn"], "or", None, Some(SourceFilter::Human), &[]),
+            vec!["frostfire"],
+        ),
+        (
+            filter(&["burn"], "or", None, Some(SourceFilter::Import), &[]),
+            vec!["fireball", "torch"],
+        ),
+        (
+            filter(&["burn"], "or", Some(true), None, &[]),
+            vec!["torch"],
+        ),
+        (
+            filter(&["burn"], "or", None, None, &["freeze", "heal"]),
+            vec!["fireball"],
+        ),
+        (filter(&["unknown"], "or", None, None, &[]), vec![]),
+        (filter(&[], "and", None, None, &[]), vec![]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::get_test_connection;
+
+    #[test]
+    fn test_find_filters_by_tags_source_and_verification() {
+        let mut conn = get_test_connection();
+        insert_test_entities(&mut conn);
+
+        for (params, expected) in test_filters() {
+            let found = find(&params, &conn).unwrap();
+            let slugs: Vec<&str> = found.iter().map(|entity| entity.slug.as_str()).collect();
+            assert_eq!(slugs, expected, "{params:?}");
+        }
+        let any = find(&test_filters()[0].0, &conn).unwrap();
+        assert_eq!(any[1].entity_type, IndexedEntityType::Item);
+        assert_eq!(any[1].tags, vec!["burn", "heal"]);
+    }
+}
@@ -1,83 +1,246 @@
 use crate::error::{Error, ErrorType};
-use std::{
-    ffi::OsString,
-    path::{Path, PathBuf},
-};
+use std::path::Path;
 
 use crate::models::CONFIG;
-use rusqlite::{CachedStatement, Connection, config::DbConfig};
+use crate::resources;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, config::DbConfig};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
 
 const MIGRATION_TABLE: &str = "migrations";
 const MIGRATION_FILE_NAME_COLUMN: &str = "name";
+const DOWN_SUFFIX: &str = ".down.sql";
+/// How many statements prepared with `prepare_cached` each connection keeps. The cached SQL
+/// texts do not depend on the request parameters, so this covers all of them.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+pub(crate) type Pool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Enables foreign keys, triggers and the write-ahead log, and makes writers wait for the
+/// lock instead of failing with `SQLITE_BUSY` right away.
+fn configure_connection(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)?;
+    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER, true)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.busy_timeout(Duration::from_millis(CONFIG.db_busy_timeout_ms))?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    crate::tag_index::install_hooks(conn);
+    Ok(())
+}
 
 pub(crate) fn get_connection() -> Result<Connection, Error> {
     // Connection::open is idempotent.
     let conn = Connection::open(&CONFIG.db_path)
         .map_err(|_| "Failed to open a connection to the database")?;
-    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)?;
-    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER, true)?;
+    configure_connection(&conn)?;
     Ok(conn)
 }
 
+/// Creates the pool of connections the server shares between requests.
+pub(crate) fn create_pool() -> Result<Pool, Error> {
+    let manager =
+        SqliteConnectionManager::file(&CONFIG.db_path).with_init(|conn| configure_connection(conn));
+    r2d2::Pool::builder()
+        .max_size(CONFIG.db_pool_size)
+        .build(manager)
+        .map_err(|err| format!("Failed to create the database connection pool: {err}").into())
+}
+
+/// Runs the closure with a pooled connection on the blocking thread pool, so slow queries
+/// do not stall the runtime threads.
+pub(crate) async fn run<T, F>(pool: &Pool, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|err| {
+            Error(
+                format!("No database connection available: {err}"),
+                ErrorType::Unavailable,
+            )
+        })?;
+        f(&mut conn)
+    })
+    .await
+    .map_err(|err| format!("The database task failed: {err}"))?
+}
+
+#[cfg(test)]
+pub(crate) fn get_test_connection() -> Connection {
+    let conn = Connection::open_in_memory().expect("Failed to open an in-memory database");
+    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)
+        .expect("Failed to enable foreign keys");
+    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER, true)
+        .expect("Failed to enable triggers");
+    synchronize_db(&conn).expect("Failed to run the migrations");
+    conn
+}
+
+/// Whether the database has neither items nor abilities, trashed ones included.
+pub(crate) fn is_empty(connection: &Connection) -> Result<bool, Error> {
+    Ok(connection.query_row(
+        "SELECT NOT EXISTS (SELECT 1 FROM items) AND NOT EXISTS (SELECT 1 FROM abilities)",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// A `v<version>.sql` migration and its optional `v<version>.down.sql` counterpart.
+#[derive(Debug)]
+struct MigrationFile {
+    version: u32,
+    name: String,
+    sql: String,
+    down_sql: Option<String>,
+}
+
+#[derive(Debug)]
+struct AppliedMigration {
+    name: String,
+    checksum: Option<String>,
+    applied_at: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct MigrationStatus {
+    pub(crate) name: String,
+    pub(crate) applied: bool,
+    pub(crate) applied_at: Option<String>,
+    /// The file changed since the migration was applied.
+    pub(crate) modified: bool,
+    /// The migration has a down-migration.
+    pub(crate) reversible: bool,
+}
+
 pub(crate) fn synchronize_db(connection: &Connection) -> Result<(), Error> {
+    synchronize_db_from(connection, Path::new(&CONFIG.db_migrations))
+}
+
+fn synchronize_db_from(connection: &Connection, dir: &Path) -> Result<(), Error> {
     create_migration_table(connection)?;
     tracing::event!(
         tracing::Level::TRACE,
         "Created the migration table if it did not exist"
     );
-    execute_missing_migrations(connection)?;
-    Ok(())
+    let files = list_migration_files(dir)?;
+    tracing::trace!("Migration files found: {files:?}");
+    let applied = list_applied_migrations(connection)?;
+    tracing::trace!("Applied migrations: {applied:?}");
+    verify_checksums(connection, &files, &applied)?;
+    execute_missing_migrations(connection, &files, &applied)
+}
+
+/// Lists the migration files in version order and whether they have been applied.
+pub(crate) fn migration_status(connection: &Connection) -> Result<Vec<MigrationStatus>, Error> {
+    create_migration_table(connection)?;
+    let applied = list_applied_migrations(connection)?;
+    list_migration_files(Path::new(&CONFIG.db_migrations))?
+        .into_iter()
+        .map(|file| {
+            let migration = applied.iter().find(|m| m.name == file.name);
+            let modified = match migration.and_then(|m| m.checksum.as_ref()) {
+                Some(checksum) => &checksum_of(&file.sql) != checksum,
+                None => false,
+            };
+            Ok(MigrationStatus {
+                applied: migration.is_some(),
+                applied_at: migration.and_then(|m| m.applied_at.clone()),
+                modified,
+                reversible: file.down_sql.is_some(),
+                name: file.name,
+            })
+        })
+        .collect()
+}
+
+/// Reverts the last `steps` applied migrations with their down-migrations.
+/// Returns the names of the reverted migrations.
+pub(crate) fn migrate_down(connection: &Connection, steps: usize) -> Result<Vec<String>, Error> {
+    create_migration_table(connection)?;
+    let files = list_migration_files(Path::new(&CONFIG.db_migrations))?;
+    let applied = list_applied_migrations(connection)?;
+    verify_checksums(connection, &files, &applied)?;
+
+    let mut reverted = Vec::new();
+    for file in files
+        .iter()
+        .rev()
+        .filter(|file| applied.iter().any(|m| m.name == file.name))
+        .take(steps)
+    {
+        let down_sql = file
+            .down_sql
+            .as_ref()
+            .ok_or_else(|| format!("The migration {} has no down-migration", file.name))?;
+        tracing::info!("Reverting the migration {}", file.name);
+        let tx = connection.unchecked_transaction()?;
+        execute_migration(&file.name, down_sql, &tx)?;
+        tx.execute(
+            &format!("DELETE FROM {MIGRATION_TABLE} WHERE {MIGRATION_FILE_NAME_COLUMN} = ?1"),
+            [&file.name],
+        )?;
+        tx.commit()?;
+        reverted.push(file.name.clone());
+    }
+    Ok(reverted)
 }
 
 fn create_migration_table(connection: &Connection) -> Result<(), Error> {
     connection
         .execute_batch(&format!(
             "BEGIN;
-                CREATE TABLE if NOT EXISTS {} ( {} TEXT PRIMARY KEY);
+                CREATE TABLE if NOT EXISTS {} ( {} TEXT PRIMARY KEY, checksum TEXT, applied_at TIMESTAMP);
                 COMMIT;
             ",
             MIGRATION_TABLE, MIGRATION_FILE_NAME_COLUMN
         ))
-        .map_err(|_| "Failed to create the migration table".into())
-        .map(|_| ())
-}
+        .map_err(|_| "Failed to create the migration table")?;
 
-fn execute_missing_migrations(connection: &Connection) -> Result<(), Error> {
-    let files = list_migration_files()?;
-    tracing::trace!("Migration files found: {files:?}");
-    let done_migrations = list_done_migrations(connection)?;
-    tracing::trace!("Done migrations: {done_migrations:?}");
-    let mut stmt = connection
-        .prepare_cached(&format!(
-            "INSERT INTO {} ({}) VALUES (?1)",
-            MIGRATION_TABLE, MIGRATION_FILE_NAME_COLUMN
-        ))
-        .map_err(|_| "Failed to prepare the cached statement inserting migration files into db")?;
-    let unexecuted_migrations = find_unexecuted_migrations(&files, &done_migrations)?;
-    unexecuted_migrations
-        .into_iter()
-        .try_for_each(|migration_file| {
-            execute_migration(migration_file, connection)?;
-            mark_migration_done(migration_file, &mut stmt)
-        })
+    // Databases created before the checksums only have the name column
+    let has_checksum: bool = connection.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM pragma_table_info('{MIGRATION_TABLE}') WHERE name = 'checksum')"),
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_checksum {
+        connection
+            .execute_batch(&format!(
+                "ALTER TABLE {MIGRATION_TABLE} ADD COLUMN checksum TEXT;
+                ALTER TABLE {MIGRATION_TABLE} ADD COLUMN applied_at TIMESTAMP;"
+            ))
+            .map_err(|_| "Failed to add the checksum to the migration table")?;
+    }
+    Ok(())
 }
 
-fn find_unexecuted_migrations<'a>(
-    files: &'a [PathBuf],
-    done_migrations: &'a [OsString],
-) -> Result<Vec<&'a PathBuf>, Error> {
-    Ok(files
-        .iter()
-        .filter(|&file| {
-            !done_migrations.contains(&file.file_name().expect("File name exists").to_os_string())
-        })
-        .collect())
+/// Parses `v<version>.sql` and `v<version>.down.sql`. Returns the version and whether the
+/// file is a down-migration.
+fn parse_file_name(name: &str) -> Option<(u32, bool)> {
+    let rest = name.strip_prefix('v')?;
+    let (version, down) = match rest.strip_suffix(DOWN_SUFFIX) {
+        Some(version) => (version, true),
+        None => (rest.strip_suffix(".sql")?, false),
+    };
+    Some((version.parse().ok()?, down))
 }
 
-fn list_migration_files() -> Result<Vec<PathBuf>, Error> {
-    let paths = std::fs::read_dir(&CONFIG.db_migrations)
-        .map_err(|_| "Failed to read migration directory")?;
-    let mut migration_files: Vec<_> = paths
+/// Reads the names and contents of the migration files in the directory. Without the
+/// directory, falls back to the migrations embedded in the binary, if any.
+fn read_migration_sources(dir: &Path) -> Result<Vec<(String, String)>, Error> {
+    let embedded = resources::embedded_migrations();
+    if !dir.exists() && !embedded.is_empty() {
+        tracing::debug!("{dir:?} does not exist, using the embedded migrations");
+        return Ok(embedded
+            .iter()
+            .map(|(name, sql)| (name.to_string(), sql.to_string()))
+            .collect());
+    }
+
+    let paths = std::fs::read_dir(dir).map_err(|_| "Failed to read migration directory")?;
+    let paths = paths
         .map(|res| {
             res.map(|entry| entry.path()).map_err(|e| {
                 Error(
@@ -87,45 +250,261 @@ fn list_migration_files() -> Result<Vec<PathBuf>, Error> {
             })
         })
         .collect::<Result<Vec<_>, Error>>()?;
-    migration_files.sort();
+    let mut sources = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if parse_file_name(&name).is_none() {
+            tracing::warn!("Ignoring {name}, migrations are named v<number>.sql");
+            continue;
+        }
+        let sql = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read the migration file {path:?}: {err}"))?;
+        sources.push((name, sql));
+    }
+    Ok(sources)
+}
+
+fn list_migration_files(dir: &Path) -> Result<Vec<MigrationFile>, Error> {
+    let mut migration_files: Vec<MigrationFile> = Vec::new();
+    let mut down_migrations = Vec::new();
+    for (name, sql) in read_migration_sources(dir)? {
+        match parse_file_name(&name) {
+            Some((version, true)) => down_migrations.push((version, name, sql)),
+            Some((version, false)) => {
+                if let Some(other) = migration_files.iter().find(|m| m.version == version) {
+                    return Err(format!(
+                        "The migrations {} and {name} have the same version",
+                        other.name
+                    )
+                    .into());
+                }
+                migration_files.push(MigrationFile {
+                    version,
+                    name,
+                    sql,
+                    down_sql: None,
+                });
+            }
+            // read_migration_sources leaves out the other files
+            None => {}
+        }
+    }
+    for (version, name, sql) in down_migrations {
+        match migration_files.iter_mut().find(|m| m.version == version) {
+            Some(migration) => migration.down_sql = Some(sql),
+            None => tracing::warn!("The down-migration {name} has no migration"),
+        }
+    }
+    // Sorting by version runs v10.sql after v9.sql
+    migration_files.sort_by_key(|migration| migration.version);
     Ok(migration_files)
 }
 
-fn list_done_migrations(connection: &Connection) -> Result<Vec<OsString>, Error> {
+fn list_applied_migrations(connection: &Connection) -> Result<Vec<AppliedMigration>, Error> {
     let mut stmt = connection
         .prepare(&format!(
-            "SELECT {} FROM {}",
+            "SELECT {}, checksum, applied_at FROM {}",
             MIGRATION_FILE_NAME_COLUMN, MIGRATION_TABLE
         ))
         .map_err(|_| "Failed to prepare the migration query")?;
-    let mut done_migrations = vec![];
-    let mut rows = stmt
-        .query([])
-        .map_err(|_| "Failed to query for done migrations")?;
-    while let Some(row) = rows
-        .next()
-        .map_err(|_| "Failed to get the next migration row")?
+    stmt.query_map([], |row| {
+        Ok(AppliedMigration {
+            name: row.get(0)?,
+            checksum: row.get(1)?,
+            applied_at: row.get(2)?,
+        })
+    })
+    .map_err(|_| "Failed to query for done migrations")?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|_| "Failed to get value from the migration row".into())
+}
+
+fn checksum_of(sql: &str) -> String {
+    format!("sha256:{:x}", Sha256::digest(sql))
+}
+
+/// Fails if an applied migration was edited. Migrations applied before the checksums were
+/// stored get the checksum of their current file.
+fn verify_checksums(
+    connection: &Connection,
+    files: &[MigrationFile],
+    applied: &[AppliedMigration],
+) -> Result<(), Error> {
+    for migration in applied {
+        let Some(file) = files.iter().find(|file| file.name == migration.name) else {
+            tracing::warn!("The applied migration {} has no file", migration.name);
+            continue;
+        };
+        let checksum = checksum_of(&file.sql);
+        match &migration.checksum {
+            Some(stored) if stored != &checksum => {
+                return Err(format!(
+                    "The migration {} was modified after it was applied. Restore the file or add a new migration",
+                    migration.name
+                )
+                .into());
+            }
+            Some(_) => {}
+            None => {
+                connection.execute(
+                    &format!(
+                        "UPDATE {MIGRATION_TABLE} SET checksum = ?1 WHERE {MIGRATION_FILE_NAME_COLUMN} = ?2"
+                    ),
+                    [&checksum, &migration.name],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies every pending migration in its own transaction, so a failing migration leaves
+/// the database as it was before it.
+fn execute_missing_migrations(
+    connection: &Connection,
+    files: &[MigrationFile],
+    applied: &[AppliedMigration],
+) -> Result<(), Error> {
+    let latest_applied = files
+        .iter()
+        .filter(|file| applied.iter().any(|m| m.name == file.name))
+        .map(|file| file.version)
+        .max();
+    for file in files
+        .iter()
+        .filter(|file| !applied.iter().any(|m| m.name == file.name))
     {
-        let migration: String = row
-            .get(0)
-            .map_err(|_| "Failed to get value from the migration row")?;
-        done_migrations.push(migration.into());
+        if latest_applied.is_some_and(|latest| latest > file.version) {
+            tracing::warn!(
+                "Applying {} after a migration with a higher version",
+                file.name
+            );
+        }
+        let tx = connection.unchecked_transaction()?;
+        execute_migration(&file.name, &file.sql, &tx)?;
+        tx.execute(
+            &format!(
+                "INSERT INTO {MIGRATION_TABLE} ({MIGRATION_FILE_NAME_COLUMN}, checksum, applied_at)
+                VALUES (?1, ?2, CURRENT_TIMESTAMP)"
+            ),
+            [&file.name, &checksum_of(&file.sql)],
+        )
+        .map_err(|_| "Failed to mark the migration as done")?;
+        tx.commit()?;
     }
-    Ok(done_migrations)
+    Ok(())
 }
 
-fn execute_migration(file: &Path, connection: &Connection) -> Result<(), Error> {
-    tracing::trace!("Executing the migration from file: {file:?}");
-    let commands =
-        std::fs::read_to_string(file).map_err(|_| "Failed to read the migration file")?;
+fn execute_migration(name: &str, sql: &str, connection: &Connection) -> Result<(), Error> {
+    tracing::trace!("Executing the migration {name}");
     connection
-        .execute_batch(&commands)
-        .map_err(|e| format!("Failed to execute the migration file: {e:?}"))?;
+        .execute_batch(sql)
+        .map_err(|e| format!("Failed to execute the migration {name}: {e:?}"))?;
     Ok(())
 }
 
-fn mark_migration_done(file: &Path, stmt: &mut CachedStatement) -> Result<(), Error> {
-    stmt.execute([file.file_name().map(|name| name.to_str()).unwrap()])
-        .map_err(|_| "Failed to mark the migration as done")?;
-    Ok(())
+/// The stored checksum of a migration, if it was applied.
+#[cfg(test)]
+fn find_applied(connection: &Connection, name: &str) -> Option<String> {
+    use rusqlite::OptionalExtension;
+    connection
+        .query_row(
+            &format!(
+                "SELECT checksum FROM {MIGRATION_TABLE} WHERE {MIGRATION_FILE_NAME_COLUMN} = ?1"
+            ),
+            [name],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn migration_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hammer-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_migrations_run_in_version_order() {
+        let dir = migration_dir(
+            "order",
+            &[
+                ("v10.sql", "INSERT INTO t VALUES (10);"),
+                ("v2.sql", "INSERT INTO t VALUES (2);"),
+                ("v1.sql", "CREATE TABLE t (v INTEGER);"),
+            ],
+        );
+        let conn = Connection::open_in_memory().unwrap();
+
+        synchronize_db_from(&conn, &dir).unwrap();
+
+        let values: Vec<i64> = conn
+            .prepare("SELECT v FROM t ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values, vec![2, 10]);
+        assert!(find_applied(&conn, "v10.sql").is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_migration_is_rolled_back_and_edits_are_detected() {
+        let dir = migration_dir(
+            "transactions",
+            &[
+                ("v1.sql", "CREATE TABLE t (v INTEGER);"),
+                (
+                    "v2.sql",
+                    "INSERT INTO t VALUES (1); INSERT INTO missing VALUES (1);",
+                ),
+            ],
+        );
+        let conn = Connection::open_in_memory().unwrap();
+
+        assert!(synchronize_db_from(&conn, &dir).is_err());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(find_applied(&conn, "v2.sql").is_none());
+
+        std::fs::write(dir.join("v1.sql"), "CREATE TABLE t (v TEXT);").unwrap();
+        let err = synchronize_db_from(&conn, &dir).unwrap_err();
+        assert!(err.0.contains("v1.sql was modified"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_locked_database_is_reported_as_unavailable() {
+        let path = std::env::temp_dir().join(format!("hammer-busy-{}.db3", std::process::id()));
+        let writer = Connection::open(&path).unwrap();
+        writer
+            .execute_batch("CREATE TABLE t (v INTEGER); BEGIN IMMEDIATE;")
+            .unwrap();
+        let other = Connection::open(&path).unwrap();
+        other.busy_timeout(Duration::ZERO).unwrap();
+
+        let err = Error::from(other.execute("INSERT INTO t VALUES (1)", []).unwrap_err());
+
+        assert!(matches!(err.1, ErrorType::Unavailable));
+        drop(writer);
+        std::fs::remove_file(path).unwrap();
+    }
 }
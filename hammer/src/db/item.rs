@@ -1,10 +1,16 @@
-use crate::error::Error;
-use rusqlite::Connection;
+use crate::db::changes::Since;
+use crate::error::{Error, ErrorType};
+use crate::tag_index;
+use rusqlite::{Connection, OptionalExtension, Transaction};
 
-use crate::models::{Item, PersistedItem};
+use crate::models::{EntityTag, IndexedEntityType, Item, PersistedItem, TagSource, TrashedEntity};
 
-pub(crate) fn insert(item: &Item, conn: &mut Connection) -> Result<(), Error> {
-    let tx = conn.transaction()?;
+/// Inserts the item as part of a transaction owned by the caller.
+pub(crate) fn insert_in_transaction(
+    item: &Item,
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<(), Error> {
     let mut stmt = tx.prepare(
         "INSERT INTO items (name, slug, wiki_url, effects_description) values (?1, ?2, ?3, ?4)",
     )?;
@@ -15,33 +21,179 @@ pub(crate) fn insert(item: &Item, conn: &mut Connection) -> Result<(), Error> {
             &item.wiki_url,
             &item.effects_description
         ])
-        .inspect_err(|err| tracing::warn!("Failed to insert an item into the table. {err:?}"))?;
+        .inspect_err(|err| tracing::warn!("Failed to insert an item into the table. {err:?}"))
+        .map_err(|err| unique_error(err, &item.name, &item.slug, tx))?;
     drop(stmt);
-    let mut stmt = tx.prepare("INSERT INTO items_tags (item_id, tag_name) VALUES (?1, ?2)")?;
-    for tag_name in &item.tags {
-        stmt.insert(rusqlite::params![id, tag_name])
-            .inspect_err(|err| {
-                tracing::warn!("Failed to insert {tag_name} to items_tags table. Err: {err:?}")
-            })?;
+    replace_tags(id, &item.tags, source, tx).inspect_err(|err| {
+        tracing::warn!(
+            "Failed to insert tags of {} to items_tags table. Err: {err:?}",
+            item.slug
+        )
+    })?;
+    Ok(())
+}
+
+/// The error of a write that failed on a constraint. Trashed items keep their unique name and
+/// slug, so reusing them fails until the item is restored or purged.
+fn unique_error(err: rusqlite::Error, name: &str, slug: &str, conn: &Connection) -> Error {
+    if err.sqlite_error_code() == Some(rusqlite::ErrorCode::ConstraintViolation) {
+        let trashed = conn
+            .prepare_cached(
+                "SELECT slug FROM items WHERE (name=?1 OR slug=?2) AND deleted_at IS NOT NULL",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_row([name, slug], |row| row.get::<_, String>(0))
+                    .optional()
+            });
+        if let Ok(Some(trashed)) = trashed {
+            return Error(
+                format!("The item {trashed} is in the trash, restore or purge it first"),
+                ErrorType::Conflict,
+            );
+        }
+    }
+    err.into()
+}
+
+fn find_id_by_slug(slug: &str, conn: &Connection) -> Result<i64, Error> {
+    let mut stmt =
+        conn.prepare_cached("SELECT id FROM items WHERE slug=?1 AND deleted_at IS NULL")?;
+    stmt.query_row([slug], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| Error(format!("Item {slug} not found"), ErrorType::NotFound))
+}
+
+/// Sets the tags of the item. Tags the item already has keep their original source.
+fn replace_tags(
+    id: i64,
+    new_tags: &[String],
+    source: &TagSource,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("SELECT tag_name FROM items_tags WHERE item_id=?1")?;
+    let current_tags = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    drop(stmt);
+
+    let mut stmt =
+        conn.prepare_cached("DELETE FROM items_tags WHERE item_id=?1 AND tag_name=?2")?;
+    for tag_name in current_tags.iter().filter(|tag| !new_tags.contains(tag)) {
+        stmt.execute(rusqlite::params![id, tag_name])?;
+    }
+    drop(stmt);
+
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO items_tags (item_id, tag_name, source, source_ref) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let mut inserted: Vec<&String> = Vec::new();
+    for tag_name in new_tags {
+        if current_tags.contains(tag_name) || inserted.contains(&tag_name) {
+            continue;
+        }
+        stmt.execute(rusqlite::params![
+            id,
+            tag_name,
+            source.kind(),
+            source.reference()
+        ])?;
+        inserted.push(tag_name);
+    }
+    drop(stmt);
+    tag_index::refresh(IndexedEntityType::Item, id, conn)
+}
+
+/// Returns the fields of the item that editors locked against imports.
+pub(crate) fn find_locks(slug: &str, conn: &Connection) -> Result<Vec<String>, Error> {
+    let id = find_id_by_slug(slug, conn)?;
+    let mut stmt =
+        conn.prepare_cached("SELECT field FROM items_field_locks WHERE item_id=?1 ORDER BY field")?;
+    let locks = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(locks)
+}
+
+/// Replaces the locked fields of the item as part of a transaction owned by the caller.
+pub(crate) fn set_locks(
+    slug: &str,
+    fields: &[String],
+    locked_by: &str,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let id = find_id_by_slug(slug, tx)?;
+    let mut stmt = tx.prepare_cached("SELECT field FROM items_field_locks WHERE item_id=?1")?;
+    let current_locks = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    drop(stmt);
+    let mut stmt =
+        tx.prepare_cached("DELETE FROM items_field_locks WHERE item_id=?1 AND field=?2")?;
+    for field in current_locks.iter().filter(|field| !fields.contains(field)) {
+        stmt.execute(rusqlite::params![id, field])?;
+    }
+    drop(stmt);
+    let mut stmt = tx.prepare_cached(
+        "INSERT OR IGNORE INTO items_field_locks (item_id, field, locked_by) VALUES (?1, ?2, ?3)",
+    )?;
+    for field in fields {
+        stmt.execute(rusqlite::params![id, field, locked_by])?;
     }
     drop(stmt);
-    tx.commit()?;
     Ok(())
 }
 
-fn from_row(row: &rusqlite::Row, conn: &Connection) -> Result<PersistedItem, Error> {
+pub(crate) fn find_tag_sources(slug: &str, conn: &Connection) -> Result<Vec<EntityTag>, Error> {
+    let id = find_id_by_slug(slug, conn)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT tag_name, source, source_ref FROM items_tags WHERE item_id=?1 ORDER BY tag_name",
+    )?;
+    let tags = stmt
+        .query_map([id], |row| {
+            Ok(EntityTag {
+                name: row.get(0)?,
+                source: row.get(1)?,
+                source_ref: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tags)
+}
+
+/// Marks the item as verified by the editor, or clears the flag if `verified_by` is `None`.
+/// Returns `false` if there is no item with the given slug.
+pub(crate) fn set_verified(
+    slug: &str,
+    verified_by: Option<&str>,
+    conn: &Connection,
+) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE items SET verified_at=CASE WHEN ?1 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, verified_by=?1 WHERE slug=?2 AND deleted_at IS NULL RETURNING id",
+    )?;
+    let id = stmt
+        .query_row(rusqlite::params![verified_by, slug], |row| row.get(0))
+        .optional()?;
+    refresh_index(id, conn)
+}
+
+/// Selects the items together with their tags as a JSON array, so loading any number of
+/// items takes a single query. Callers add the `WHERE` clause and then `GROUP_BY_ID`.
+const SELECT_WITH_TAGS: &str = "SELECT i.id, i.name, i.slug, i.wiki_url, i.effects_description, \
+    i.verified_at IS NOT NULL, json_group_array(t.tag_name) FILTER (WHERE t.tag_name IS NOT NULL), \
+    strftime('%Y-%m-%dT%H:%M:%SZ', i.created_at), strftime('%Y-%m-%dT%H:%M:%SZ', i.updated_at) \
+    FROM items i LEFT JOIN items_tags t ON t.item_id = i.id";
+const GROUP_BY_ID: &str = "GROUP BY i.id ORDER BY i.id";
+
+fn from_row(row: &rusqlite::Row) -> Result<PersistedItem, Error> {
     let id = row.get(0)?;
     let name = row.get(1)?;
     let slug = row.get(2)?;
     let wiki_url = row.get(3)?;
     let effects_description = row.get(4).unwrap_or("".to_string());
-    let mut tags = Vec::default();
-    let mut stmt = conn.prepare("SELECT item_id,tag_name FROM items_tags WHERE item_id=?1")?;
-    let mut rows = stmt.query(rusqlite::params![id])?;
-    while let Some(row) = rows.next()? {
-        let tag_name = row.get(1)?;
-        tags.push(tag_name);
-    }
+    let verified = row.get(5)?;
+    let tags = super::parse_tags(&row.get::<_, String>(6)?)?;
+    let created_at = row.get(7)?;
+    let updated_at = row.get(8)?;
     Ok(PersistedItem {
         id,
         name,
@@ -49,92 +201,279 @@ fn from_row(row: &rusqlite::Row, conn: &Connection) -> Result<PersistedItem, Err
         wiki_url,
         tags,
         effects_description,
+        verified,
+        created_at,
+        updated_at,
     })
 }
 
 pub(crate) fn find_by_slug(slug: &str, conn: &Connection) -> Result<Option<PersistedItem>, Error> {
-    let mut stmt =
-        conn.prepare("SELECT id,name,slug,wiki_url,effects_description FROM items WHERE slug=?1")?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE i.slug=?1 AND i.deleted_at IS NULL {GROUP_BY_ID}"
+    ))?;
     let mut rows = stmt.query(rusqlite::params![slug])?;
-    Ok(rows.next()?.and_then(|row| from_row(row, conn).ok()))
+    Ok(rows.next()?.and_then(|row| from_row(row).ok()))
 }
 
 pub(crate) fn find_all(conn: &Connection) -> Result<Vec<PersistedItem>, Error> {
-    let mut stmt = conn.prepare("SELECT id,name,slug,wiki_url,effects_description FROM items")?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE i.deleted_at IS NULL {GROUP_BY_ID}"
+    ))?;
     let mut rows = stmt.query([])?;
     let mut items = Vec::new();
     while let Some(row) = rows.next()? {
-        items.push(from_row(row, conn)?);
+        items.push(from_row(row)?);
     }
     Ok(items)
 }
 
-pub(crate) fn delete(slug: &str, conn: &Connection) -> Result<(), Error> {
-    let mut stmt = conn.prepare("DELETE FROM items WHERE slug=?1")?;
-    stmt.execute(rusqlite::params![slug])?;
-    Ok(())
+/// Finds the live items created or changed after `since`, or all of them if it is `None`.
+pub(crate) fn find_updated_since(
+    since: Option<&Since>,
+    conn: &Connection,
+) -> Result<Vec<PersistedItem>, Error> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_WITH_TAGS} WHERE i.deleted_at IS NULL \
+        AND (?1 IS NULL OR i.updated_at >= ?1) AND (?2 IS NULL OR i.change_seq > ?2) {GROUP_BY_ID}"
+    ))?;
+    let (timestamp, seq) = Since::bounds(since);
+    let mut rows = stmt.query(rusqlite::params![timestamp, seq])?;
+    let mut items = Vec::new();
+    while let Some(row) = rows.next()? {
+        items.push(from_row(row)?);
+    }
+    Ok(items)
 }
 
-pub(crate) fn update(slug: &str, item: &Item, conn: &mut Connection) -> Result<(), Error> {
-    let tx = conn.transaction()?;
-    let mut stmt = tx.prepare(
-        "UPDATE items set name=?1, slug=?2, wiki_url=?3, effects_description=?4 WHERE slug=?5",
-    )?;
-    let _ = stmt.execute(rusqlite::params![
-        item.name,
-        item.slug,
-        item.wiki_url,
-        item.effects_description,
-        slug
-    ])?;
-    drop(stmt);
+/// Finds the slug of the item reconciled with the game data object.
+pub(crate) fn find_slug_by_game_data_id(
+    game_data_id: &str,
+    conn: &Connection,
+) -> Result<Option<String>, Error> {
     let mut stmt =
-        tx.prepare("DELETE FROM items_tags WHERE item_id=(SELECT id FROM items WHERE slug=?1)")?;
-    let _ = stmt.execute(rusqlite::params![slug])?;
-    drop(stmt);
-    let mut stmt = tx.prepare("INSERT INTO items_tags (item_id, tag_name) VALUES ((SELECT id FROM items WHERE slug=?1), ?2)")?;
-    for tag_name in &item.tags {
-        stmt.insert(rusqlite::params![slug, tag_name])?;
-    }
-    drop(stmt);
-    tx.commit()?;
+        conn.prepare_cached("SELECT slug FROM items WHERE game_data_id=?1 AND deleted_at IS NULL")?;
+    Ok(stmt
+        .query_row([game_data_id], |row| row.get(0))
+        .optional()?)
+}
+
+/// Records the game data object the item is reconciled with, so later imports of the game
+/// data find it by that ID even if its name, and so its slug, changed.
+pub(crate) fn set_game_data_id(
+    slug: &str,
+    game_data_id: &str,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("UPDATE items SET game_data_id=?2 WHERE slug=?1")?;
+    stmt.execute([slug, game_data_id])?;
     Ok(())
 }
 
-pub(crate) fn find_by_ids(ids: &[i64], conn: &Connection) -> Result<Vec<PersistedItem>, Error> {
-    let placeholder = ids
-        .iter()
-        .map(|id| format!("{id}"))
-        .collect::<Vec<String>>()
-        .join(",");
-    let mut stmt = conn.prepare_cached(&format!(
-        "SELECT id,name,slug,wiki_url FROM items WHERE id IN ({placeholder})"
-    ))?;
+/// Moves the item to the trash.
+/// Returns `false` if there is no item with the given slug outside of the trash.
+pub(crate) fn delete(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE items SET deleted_at=CURRENT_TIMESTAMP WHERE slug=?1 AND deleted_at IS NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
+}
+
+pub(crate) fn find_deleted(conn: &Connection) -> Result<Vec<TrashedEntity>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT name,slug,deleted_at FROM items WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )?;
     let mut rows = stmt.query([])?;
     let mut items = Vec::new();
     while let Some(row) = rows.next()? {
-        items.push(from_row(row, conn)?);
+        items.push(TrashedEntity {
+            name: row.get(0)?,
+            slug: row.get(1)?,
+            deleted_at: row.get(2)?,
+            entity_type: IndexedEntityType::Item,
+        });
     }
     Ok(items)
 }
 
-pub(crate) fn update_tags_by_slug(
+/// Returns `false` if there is no deleted item with the given slug.
+pub(crate) fn restore(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE items SET deleted_at=NULL WHERE slug=?1 AND deleted_at IS NOT NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
+}
+
+/// Permanently removes a deleted item together with its tags.
+/// Returns `false` if there is no deleted item with the given slug.
+pub(crate) fn purge(slug: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "DELETE FROM items WHERE slug=?1 AND deleted_at IS NOT NULL RETURNING id",
+    )?;
+    let id = stmt.query_row([slug], |row| row.get(0)).optional()?;
+    refresh_index(id, conn)
+}
+
+/// Refreshes the item in the tag index if the write found it.
+/// Returns whether there was such an item.
+fn refresh_index(id: Option<i64>, conn: &Connection) -> Result<bool, Error> {
+    match id {
+        Some(id) => tag_index::refresh(IndexedEntityType::Item, id, conn).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Updates the item as part of a transaction owned by the caller.
+pub(crate) fn update_in_transaction(
     slug: &str,
-    new_tags: Vec<String>,
-    conn: &mut Connection,
-) -> Result<Vec<String>, Error> {
-    let tx = conn.transaction()?;
-    let mut stmt = tx.prepare_cached(
-        "DELETE FROM items_tags WHERE item_id=(SELECT id FROM items WHERE slug=?1)",
+    item: &Item,
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let id = find_id_by_slug(slug, tx)?;
+    let mut stmt = tx.prepare(
+        "UPDATE items set name=?1, slug=?2, wiki_url=?3, effects_description=?4 WHERE id=?5",
     )?;
-    stmt.execute([slug])?;
+    let _ = stmt
+        .execute(rusqlite::params![
+            item.name,
+            item.slug,
+            item.wiki_url,
+            item.effects_description,
+            id
+        ])
+        .map_err(|err| unique_error(err, &item.name, &item.slug, tx))?;
     drop(stmt);
+    replace_tags(id, &item.tags, source, tx)?;
+    Ok(())
+}
+
+/// Sets the tags of the item as part of a transaction owned by the caller.
+pub(crate) fn update_tags_in_transaction(
+    slug: &str,
+    new_tags: &[String],
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let id = find_id_by_slug(slug, tx)?;
+    replace_tags(id, new_tags, source, tx)
+}
 
-    let mut stmt = tx.prepare_cached("INSERT INTO items_tags (item_id, tag_name) VALUES ((SELECT id FROM items WHERE slug=?1), ?2)")?;
-    for tag in &new_tags {
-        stmt.execute([slug, tag])?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_item() -> Item {
+        Item {
+            name: "Drawn in Spring".to_string(),
+            slug: "drawn-in-spring".to_string(),
+            wiki_url: "https://example.com/drawn-in-spring".to_string(),
+            tags: vec!["freeze".to_string()],
+            effects_description: String::new(),
+        }
+    }
+
+    fn insert_test_item(conn: &mut Connection) {
+        let tx = conn.transaction().unwrap();
+        insert_in_transaction(&test_item(), &TagSource::Import("test".to_string()), &tx).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        let mut conn = crate::db::get_test_connection();
+        insert_test_item(&mut conn);
+
+        assert!(delete("drawn-in-spring", &conn).unwrap());
+        assert!(!delete("drawn-in-spring", &conn).unwrap());
+        assert!(find_by_slug("drawn-in-spring", &conn).unwrap().is_none());
+        assert!(find_all(&conn).unwrap().is_empty());
+        let trash = find_deleted(&conn).unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].slug, "drawn-in-spring");
+
+        assert!(restore("drawn-in-spring", &conn).unwrap());
+        let item = find_by_slug("drawn-in-spring", &conn).unwrap().unwrap();
+        assert_eq!(item.tags, vec!["freeze".to_string()]);
+        assert!(!restore("drawn-in-spring", &conn).unwrap());
+    }
+
+    #[test]
+    fn test_purge_only_removes_deleted_items() {
+        let mut conn = crate::db::get_test_connection();
+        insert_test_item(&mut conn);
+
+        assert!(!purge("drawn-in-spring", &conn).unwrap());
+        delete("drawn-in-spring", &conn).unwrap();
+        assert!(purge("drawn-in-spring", &conn).unwrap());
+        assert!(find_deleted(&conn).unwrap().is_empty());
+        let tags: i64 = conn
+            .query_row("SELECT COUNT(*) FROM items_tags", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tags, 0);
+    }
+
+    #[test]
+    fn test_reusing_the_slug_of_a_trashed_item_is_a_conflict() {
+        let mut conn = crate::db::get_test_connection();
+        insert_test_item(&mut conn);
+        delete("drawn-in-spring", &conn).unwrap();
+        let source = TagSource::Editor("editor@example.com".to_string());
+        let tx = conn.transaction().unwrap();
+
+        let err = insert_in_transaction(&test_item(), &source, &tx).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict), "{err:?}");
+        assert!(err.0.contains("drawn-in-spring is in the trash"));
+        let torch = Item {
+            name: "Torch".to_string(),
+            slug: "torch".to_string(),
+            ..test_item()
+        };
+        insert_in_transaction(&torch, &source, &tx).unwrap();
+        let err = update_in_transaction("torch", &test_item(), &source, &tx).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict), "{err:?}");
+    }
+
+    #[test]
+    fn test_update_tags_keeps_provenance_of_unchanged_tags() {
+        let mut conn = crate::db::get_test_connection();
+        insert_test_item(&mut conn);
+
+        let source = TagSource::Editor("editor@example.com".to_string());
+        let new_tags = vec!["freeze".to_string(), "burn".to_string()];
+        let tx = conn.transaction().unwrap();
+        update_tags_in_transaction("drawn-in-spring", &new_tags, &source, &tx).unwrap();
+        tx.commit().unwrap();
+
+        let tags = find_tag_sources("drawn-in-spring", &conn).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].name, "burn");
+        assert_eq!(tags[0].source, "editor");
+        assert_eq!(tags[0].source_ref.as_deref(), Some("editor@example.com"));
+        assert_eq!(tags[1].name, "freeze");
+        assert_eq!(tags[1].source, "import");
+    }
+
+    #[test]
+    fn test_tag_changes_touch_the_item() {
+        let mut conn = crate::db::get_test_connection();
+        insert_test_item(&mut conn);
+        // The timestamp trigger would overwrite the old timestamp
+        let triggers = rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER;
+        conn.set_db_config(triggers, false).unwrap();
+        conn.execute("UPDATE items SET updated_at = '2000-01-01 00:00:00'", [])
+            .unwrap();
+        conn.set_db_config(triggers, true).unwrap();
+
+        let source = TagSource::Editor("editor@example.com".to_string());
+        let tx = conn.transaction().unwrap();
+        update_tags_in_transaction("drawn-in-spring", &[], &source, &tx).unwrap();
+        tx.commit().unwrap();
+
+        let updated_at: String = conn
+            .query_row("SELECT updated_at FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(updated_at, "2000-01-01 00:00:00");
     }
-    drop(stmt);
-    tx.commit()?;
-    Ok(new_tags)
 }
@@ -1,6 +1,118 @@
+use crate::error;
+
 pub(crate) mod ability;
+pub(crate) mod changes;
+pub(crate) mod import_run;
+pub(crate) mod indexed;
 mod init;
 pub(crate) mod item;
 pub(crate) mod tag;
+pub(crate) mod tag_proposal;
+pub(crate) mod token;
+pub(crate) mod user;
+pub(crate) mod webhook;
+
+#[cfg(test)]
+pub(crate) use init::get_test_connection;
+pub(crate) use init::{
+    Pool, create_pool, get_connection, is_empty, migrate_down, migration_status, run,
+    synchronize_db,
+};
+
+/// Parses the JSON array that `json_group_array` builds from the tag names of an entity.
+fn parse_tags(json: &str) -> Result<Vec<String>, error::Error> {
+    serde_json::from_str(json)
+        .map_err(|err| format!("Failed to parse the tags {json}: {err}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AbbreviatedAbility, Item, TagSource};
+    use rusqlite::Connection;
+    use rusqlite::trace::{TraceEvent, TraceEventCodes};
+    use std::cell::Cell;
+
+    thread_local! {
+        static STATEMENTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count_statement(event: TraceEvent) {
+        if let TraceEvent::Stmt(..) = event {
+            STATEMENTS.set(STATEMENTS.get() + 1);
+        }
+    }
+
+    /// The number of statements `f` runs on the connection.
+    fn count_statements<T>(conn: &mut Connection, f: impl FnOnce(&Connection) -> T) -> usize {
+        conn.trace_v2(TraceEventCodes::SQLITE_TRACE_STMT, Some(count_statement));
+        STATEMENTS.set(0);
+        f(conn);
+        conn.trace_v2(TraceEventCodes::empty(), None);
+        STATEMENTS.get()
+    }
+
+    fn insert_entities(range: std::ops::Range<usize>, conn: &mut Connection) {
+        let source = TagSource::Editor("editor@example.com".to_string());
+        let tx = conn.transaction().unwrap();
+        for i in range {
+            let tags = vec![format!("tag-{i}"), format!("tag-{}", i + 1)];
+            item::insert_in_transaction(
+                &Item {
+                    name: format!("Item {i}"),
+                    slug: format!("item-{i}"),
+                    wiki_url: String::new(),
+                    tags: tags.clone(),
+                    effects_description: String::new(),
+                },
+                &source,
+                &tx,
+            )
+            .unwrap();
+            ability::insert_abbreviated_ability(
+                &AbbreviatedAbility {
+                    name: format!("Ability {i}"),
+                    slug: format!("ability-{i}"),
+                    tags,
+                    wiki_url: String::new(),
+                },
+                &source,
+                &tx,
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_loading_entities_takes_a_constant_number_of_queries() {
+        let mut conn = get_test_connection();
+        for i in 0..=20 {
+            tag::insert(&format!("tag-{i}"), "", &conn).unwrap();
+        }
+
+        insert_entities(0..2, &mut conn);
+        let few = count_statements(&mut conn, |conn| {
+            item::find_all(conn).unwrap();
+            ability::find_all(conn).unwrap();
+        });
+        insert_entities(2..20, &mut conn);
+        let many = count_statements(&mut conn, |conn| {
+            let items = item::find_all(conn).unwrap();
+            let abilities = ability::find_all(conn).unwrap();
+            assert_eq!(items.len(), 20);
+            assert_eq!(abilities[19].tags, vec!["tag-19", "tag-20"]);
+            let params = crate::models::FilterParams {
+                tags: vec!["tag-1".to_string()],
+                filter_logic: "or".to_string(),
+                verified: None,
+                source: None,
+                exclude_tags: Vec::new(),
+            };
+            assert_eq!(indexed::find(&params, conn).unwrap().len(), 4);
+        });
 
-pub(crate) use init::{get_connection, synchronize_db};
+        assert_eq!(few, 2);
+        assert_eq!(many, 3);
+    }
+}
@@ -20,3 +20,20 @@ pub(crate) fn find_all(conn: &Connection) -> Result<Vec<Tag>, Error> {
     }
     Ok(tags)
 }
+
+pub(crate) fn insert(name: &str, description: &str, conn: &Connection) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("INSERT INTO tags (name, description) VALUES (?1, ?2)")?;
+    stmt.execute([name, description])?;
+    Ok(())
+}
+
+/// Returns whether the description changed.
+pub(crate) fn update_description(
+    name: &str,
+    description: &str,
+    conn: &Connection,
+) -> Result<bool, Error> {
+    let mut stmt = conn
+        .prepare_cached("UPDATE tags SET description=?2 WHERE name=?1 AND description IS NOT ?2")?;
+    Ok(stmt.execute([name, description])? > 0)
+}
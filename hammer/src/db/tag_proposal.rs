@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::models::{NewTagProposal, ProposalStatus, TagProposal};
+use rusqlite::{Connection, OptionalExtension, Row, Rows, TransactionBehavior};
+
+/// Added and removed tags of a proposal.
+type TagChanges = (Vec<String>, Vec<String>);
+
+const SELECT_PROPOSALS: &str = "SELECT id, entity_type, entity_slug, comment, author_email, status, reviewer_email, created_at, reviewed_at FROM tag_proposals";
+
+fn from_row(row: &Row) -> Result<TagProposal, Error> {
+    let entity_type: String = row.get(1)?;
+    let status: String = row.get(5)?;
+    Ok(TagProposal {
+        id: row.get(0)?,
+        entity_type: entity_type.parse()?,
+        slug: row.get(2)?,
+        add: Vec::default(),
+        remove: Vec::default(),
+        comment: row.get(3)?,
+        author_email: row.get(4)?,
+        status: status.parse()?,
+        reviewer_email: row.get(6)?,
+        created_at: row.get(7)?,
+        reviewed_at: row.get(8)?,
+    })
+}
+
+pub(crate) fn insert(
+    proposal: &NewTagProposal,
+    author_email: &str,
+    conn: &mut Connection,
+) -> Result<i64, Error> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO tag_proposals (entity_type, entity_slug, comment, author_email) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let id = stmt.insert(rusqlite::params![
+        proposal.entity_type.as_str(),
+        &proposal.slug,
+        &proposal.comment,
+        author_email
+    ])?;
+    drop(stmt);
+    let mut stmt = tx.prepare(
+        "INSERT INTO tag_proposal_changes (proposal_id, tag_name, action) VALUES (?1, ?2, ?3)",
+    )?;
+    for tag in &proposal.add {
+        stmt.execute(rusqlite::params![id, tag, "add"])
+            .inspect_err(|err| tracing::warn!("Failed to insert proposed tag {tag}. {err:?}"))?;
+    }
+    for tag in &proposal.remove {
+        stmt.execute(rusqlite::params![id, tag, "remove"])
+            .inspect_err(|err| tracing::warn!("Failed to insert proposed tag {tag}. {err:?}"))?;
+    }
+    drop(stmt);
+    tx.commit()?;
+    Ok(id)
+}
+
+pub(crate) fn find_by_id(id: i64, conn: &Connection) -> Result<Option<TagProposal>, Error> {
+    let mut stmt = conn.prepare_cached(&format!("{SELECT_PROPOSALS} WHERE id=?1"))?;
+    let proposal = stmt
+        .query_row([id], |row| Ok(from_row(row)))
+        .optional()?
+        .transpose()?;
+    match proposal {
+        Some(mut proposal) => {
+            let mut stmt = conn.prepare_cached(
+                "SELECT proposal_id, tag_name, action FROM tag_proposal_changes WHERE proposal_id=?1",
+            )?;
+            let mut changes = collect_changes(stmt.query([proposal.id])?)?;
+            if let Some((add, remove)) = changes.remove(&proposal.id) {
+                proposal.add = add;
+                proposal.remove = remove;
+            }
+            Ok(Some(proposal))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn find_by_status(
+    status: ProposalStatus,
+    conn: &Connection,
+) -> Result<Vec<TagProposal>, Error> {
+    let mut stmt =
+        conn.prepare_cached(&format!("{SELECT_PROPOSALS} WHERE status=?1 ORDER BY id"))?;
+    let mut rows = stmt.query([status.as_str()])?;
+    let mut proposals = Vec::new();
+    while let Some(row) = rows.next()? {
+        proposals.push(from_row(row)?);
+    }
+    drop(rows);
+    drop(stmt);
+    let mut stmt = conn.prepare_cached(
+        "SELECT c.proposal_id, c.tag_name, c.action FROM tag_proposal_changes c JOIN tag_proposals p ON p.id = c.proposal_id WHERE p.status=?1",
+    )?;
+    let mut changes = collect_changes(stmt.query([status.as_str()])?)?;
+    for proposal in &mut proposals {
+        if let Some((add, remove)) = changes.remove(&proposal.id) {
+            proposal.add = add;
+            proposal.remove = remove;
+        }
+    }
+    Ok(proposals)
+}
+
+/// Groups the added and removed tags of `(proposal_id, tag_name, action)` rows by proposal id.
+fn collect_changes(mut rows: Rows) -> Result<HashMap<i64, TagChanges>, Error> {
+    let mut changes: HashMap<i64, TagChanges> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let (add, remove) = changes.entry(row.get(0)?).or_default();
+        let action: String = row.get(2)?;
+        match action.as_str() {
+            "add" => add.push(row.get(1)?),
+            _ => remove.push(row.get(1)?),
+        }
+    }
+    Ok(changes)
+}
+
+/// Moves a pending proposal to the given status.
+/// Returns `false` if the proposal is not pending anymore.
+pub(crate) fn review(
+    id: i64,
+    status: ProposalStatus,
+    reviewer_email: &str,
+    conn: &Connection,
+) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE tag_proposals SET status=?1, reviewer_email=?2, reviewed_at=CURRENT_TIMESTAMP WHERE id=?3 AND status='pending'",
+    )?;
+    Ok(stmt.execute(rusqlite::params![status.as_str(), reviewer_email, id])? > 0)
+}
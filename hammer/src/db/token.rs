@@ -0,0 +1,135 @@
+use crate::error::Error;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+
+/// Stores the hash of a new refresh token of the user, and drops the expired ones.
+pub(crate) fn insert_refresh_token(
+    token_hash: &str,
+    email: &str,
+    lifetime_seconds: u64,
+    conn: &Connection,
+) -> Result<(), Error> {
+    conn.prepare_cached("DELETE FROM refresh_tokens WHERE expires_at < CURRENT_TIMESTAMP")?
+        .execute([])?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO refresh_tokens (token_hash, email, expires_at) VALUES (?1, ?2, datetime('now', '+' || ?3 || ' seconds'))",
+    )?;
+    stmt.execute(rusqlite::params![token_hash, email, lifetime_seconds])?;
+    Ok(())
+}
+
+/// How long a replaced refresh token can still be used, so that parallel requests of one client
+/// that send the same token do not look like a stolen token being reused.
+const GRACE_SECONDS: u32 = 30;
+
+/// Marks the refresh token as replaced and returns the email of its user, or `None` if the
+/// token is unknown or expired. A token replaced more than [`GRACE_SECONDS`] ago is only used
+/// again if it was stolen, so that deletes every refresh token of the user.
+pub(crate) fn take_refresh_token(
+    token_hash: &str,
+    conn: &mut Connection,
+) -> Result<Option<String>, Error> {
+    // Immediate, so that a concurrent refresh of the same token waits for this one
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let found: Option<(String, Option<bool>, bool)> = tx
+        .prepare_cached(
+            "SELECT email, revoked_at >= datetime('now', '-' || ?2 || ' seconds'), expires_at < CURRENT_TIMESTAMP FROM refresh_tokens WHERE token_hash=?1",
+        )?
+        .query_row(rusqlite::params![token_hash, GRACE_SECONDS], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .optional()?;
+    let email = match found {
+        Some((_, _, true)) | None => None,
+        Some((email, None, false)) => {
+            tx.prepare_cached(
+                "UPDATE refresh_tokens SET revoked_at=CURRENT_TIMESTAMP WHERE token_hash=?1",
+            )?
+            .execute([token_hash])?;
+            Some(email)
+        }
+        Some((email, Some(true), false)) => {
+            tracing::debug!("A refresh token of {email} was used again within the grace period");
+            Some(email)
+        }
+        Some((email, Some(false), false)) => {
+            tracing::warn!("A replaced refresh token of {email} was used, deleting all of them");
+            delete_refresh_tokens_of(&email, &tx)?;
+            None
+        }
+    };
+    tx.commit()?;
+    Ok(email)
+}
+
+/// Deletes the refresh token, e.g. on logout, so that it cannot be used even within the grace
+/// period.
+pub(crate) fn delete_refresh_token(token_hash: &str, conn: &Connection) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("DELETE FROM refresh_tokens WHERE token_hash=?1")?;
+    stmt.execute([token_hash])?;
+    Ok(())
+}
+
+/// Deletes the refresh tokens of the user. Returns the number of deleted tokens.
+pub(crate) fn delete_refresh_tokens_of(email: &str, conn: &Connection) -> Result<usize, Error> {
+    let mut stmt = conn.prepare_cached("DELETE FROM refresh_tokens WHERE email=?1")?;
+    Ok(stmt.execute([email])?)
+}
+
+/// Adds an access token to the revocation list until it expires at `expires_at`, in seconds
+/// since the epoch, and drops the tokens that expired since.
+pub(crate) fn revoke(jti: &str, expires_at: i64, conn: &Connection) -> Result<(), Error> {
+    conn.prepare_cached("DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP")?
+        .execute([])?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR IGNORE INTO revoked_tokens (jti, expires_at) VALUES (?1, datetime(?2, 'unixepoch'))",
+    )?;
+    stmt.execute(rusqlite::params![jti, expires_at])?;
+    Ok(())
+}
+
+pub(crate) fn is_revoked(jti: &str, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached("SELECT 1 FROM revoked_tokens WHERE jti=?1")?;
+    Ok(stmt.query_row([jti], |_| Ok(())).optional()?.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::get_test_connection;
+
+    #[test]
+    fn test_refresh_tokens_are_single_use() {
+        let mut conn = get_test_connection();
+        insert_refresh_token("first", "editor@example.com", 60, &conn).unwrap();
+        insert_refresh_token("second", "editor@example.com", 60, &conn).unwrap();
+        insert_refresh_token("expired", "editor@example.com", 0, &conn).unwrap();
+        conn.execute(
+            "UPDATE refresh_tokens SET expires_at = datetime('now', '-1 second') WHERE token_hash = 'expired'",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(
+            take_refresh_token("first", &mut conn).unwrap().as_deref(),
+            Some("editor@example.com")
+        );
+        assert_eq!(take_refresh_token("expired", &mut conn).unwrap(), None);
+        assert_eq!(take_refresh_token("unknown", &mut conn).unwrap(), None);
+        // A parallel request with the same token is still in the grace period
+        assert!(take_refresh_token("first", &mut conn).unwrap().is_some());
+
+        // Using the first one after the grace period deletes the second one too
+        conn.execute(
+            "UPDATE refresh_tokens SET revoked_at = datetime('now', '-1 minute') WHERE token_hash = 'first'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(take_refresh_token("first", &mut conn).unwrap(), None);
+        assert_eq!(take_refresh_token("second", &mut conn).unwrap(), None);
+
+        assert!(!is_revoked("jti", &conn).unwrap());
+        revoke("jti", chrono::Utc::now().timestamp() + 60, &conn).unwrap();
+        revoke("jti", chrono::Utc::now().timestamp() + 60, &conn).unwrap();
+        assert!(is_revoked("jti", &conn).unwrap());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::auth::{Role, User};
+use crate::error::{Error, ErrorType};
+use rusqlite::{Connection, OptionalExtension};
+
+pub(crate) fn find_all(conn: &Connection) -> Result<Vec<User>, Error> {
+    let mut stmt = conn.prepare_cached("SELECT email, role FROM users ORDER BY email")?;
+    let mut rows = stmt.query([])?;
+    let mut users = Vec::new();
+    while let Some(row) = rows.next()? {
+        users.push(User {
+            email: row.get(0)?,
+            role: row.get(1)?,
+        });
+    }
+    Ok(users)
+}
+
+/// The role of the user, or viewer if they are not in the users table.
+pub(crate) fn find_role(email: &str, conn: &Connection) -> Result<Role, Error> {
+    let mut stmt = conn.prepare_cached("SELECT role FROM users WHERE email=?1")?;
+    let role = stmt.query_row([email], |row| row.get(0)).optional()?;
+    Ok(role.unwrap_or(Role::Viewer))
+}
+
+pub(crate) fn insert(email: &str, role: &Role, conn: &Connection) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("INSERT INTO users (email, role) VALUES (?1, ?2)")?;
+    stmt.execute([email, role.as_str()])
+        .map_err(|err| match err {
+            rusqlite::Error::SqliteFailure(failure, _)
+                if failure.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Error(format!("User {email} already exists"), ErrorType::Conflict)
+            }
+            err => err.into(),
+        })?;
+    Ok(())
+}
+
+pub(crate) fn set_role(email: &str, role: &Role, conn: &Connection) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached("UPDATE users SET role=?2 WHERE email=?1")?;
+    if stmt.execute([email, role.as_str()])? == 0 {
+        return Err(Error(
+            format!("User {email} does not exist"),
+            ErrorType::NotFound,
+        ));
+    }
+    Ok(())
+}
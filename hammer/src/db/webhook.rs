@@ -0,0 +1,141 @@
+use crate::error::Error;
+use crate::models::{DeliveryStatus, NewWebhook, PendingDelivery, Webhook, WebhookDelivery};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+const SELECT_DELIVERIES: &str = "SELECT id, webhook_id, payload, status, attempts, response_status, error, created_at, next_attempt_at, finished_at FROM webhook_deliveries";
+
+fn webhook_from_row(row: &Row) -> rusqlite::Result<Webhook> {
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        created_by: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+fn delivery_from_row(row: &Row) -> Result<WebhookDelivery, Error> {
+    Ok(WebhookDelivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        payload: row.get(2)?,
+        status: row.get::<_, String>(3)?.parse::<DeliveryStatus>()?,
+        attempts: row.get(4)?,
+        response_status: row.get(5)?,
+        error: row.get(6)?,
+        created_at: row.get(7)?,
+        next_attempt_at: row.get(8)?,
+        finished_at: row.get(9)?,
+    })
+}
+
+pub(crate) fn insert(
+    webhook: &NewWebhook,
+    created_by: &str,
+    conn: &Connection,
+) -> Result<Webhook, Error> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO webhooks (url, secret, created_by) VALUES (?1, ?2, ?3) RETURNING id, url, created_by, created_at",
+    )?;
+    Ok(stmt.query_row(
+        rusqlite::params![webhook.url, webhook.secret, created_by],
+        webhook_from_row,
+    )?)
+}
+
+pub(crate) fn find_all(conn: &Connection) -> Result<Vec<Webhook>, Error> {
+    let mut stmt =
+        conn.prepare_cached("SELECT id, url, created_by, created_at FROM webhooks ORDER BY id")?;
+    let webhooks = stmt
+        .query_map([], webhook_from_row)?
+        .collect::<Result<_, _>>()?;
+    Ok(webhooks)
+}
+
+/// Deletes the webhook together with its delivery log. Returns false if there is no such
+/// webhook.
+pub(crate) fn delete(id: i64, conn: &Connection) -> Result<bool, Error> {
+    let mut stmt = conn.prepare_cached("DELETE FROM webhooks WHERE id=?1")?;
+    Ok(stmt.execute([id])? > 0)
+}
+
+/// Queues a delivery of the payload to every webhook. Returns the number of deliveries.
+pub(crate) fn enqueue(payload: &str, conn: &Connection) -> Result<usize, Error> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO webhook_deliveries (webhook_id, payload) SELECT id, ?1 FROM webhooks",
+    )?;
+    Ok(stmt.execute([payload])?)
+}
+
+/// Finds the pending deliveries whose next attempt is due, oldest first.
+pub(crate) fn find_due(limit: usize, conn: &Connection) -> Result<Vec<PendingDelivery>, Error> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT d.id, w.url, w.secret, d.payload, d.attempts FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id WHERE d.status = 'pending' AND d.next_attempt_at <= CURRENT_TIMESTAMP ORDER BY d.id LIMIT ?1",
+    )?;
+    let deliveries = stmt
+        .query_map([limit], |row| {
+            Ok(PendingDelivery {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                secret: row.get(2)?,
+                payload: row.get(3)?,
+                attempts: row.get(4)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(deliveries)
+}
+
+pub(crate) fn mark_delivered(
+    id: i64,
+    response_status: u16,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE webhook_deliveries SET status='delivered', attempts=attempts+1, response_status=?1, error=NULL, next_attempt_at=NULL, finished_at=CURRENT_TIMESTAMP WHERE id=?2",
+    )?;
+    stmt.execute(rusqlite::params![response_status, id])?;
+    Ok(())
+}
+
+/// Records a failed attempt. The delivery is attempted again after `retry_in` seconds, or
+/// marked as failed for good if it is `None`.
+pub(crate) fn mark_attempt_failed(
+    id: i64,
+    response_status: Option<u16>,
+    error: &str,
+    retry_in: Option<u64>,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE webhook_deliveries SET attempts=attempts+1, response_status=?1, error=?2, \
+        status=CASE WHEN ?3 IS NULL THEN 'failed' ELSE 'pending' END, \
+        next_attempt_at=CASE WHEN ?3 IS NULL THEN NULL ELSE datetime('now', '+' || ?3 || ' seconds') END, \
+        finished_at=CASE WHEN ?3 IS NULL THEN CURRENT_TIMESTAMP END WHERE id=?4",
+    )?;
+    stmt.execute(rusqlite::params![response_status, error, retry_in, id])?;
+    Ok(())
+}
+
+/// The latest deliveries to the webhook, newest first, or `None` if there is no such webhook.
+pub(crate) fn find_deliveries(
+    webhook_id: i64,
+    limit: usize,
+    conn: &Connection,
+) -> Result<Option<Vec<WebhookDelivery>>, Error> {
+    let exists = conn
+        .prepare_cached("SELECT 1 FROM webhooks WHERE id=?1")?
+        .query_row([webhook_id], |_| Ok(()))
+        .optional()?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let mut stmt = conn.prepare_cached(&format!(
+        "{SELECT_DELIVERIES} WHERE webhook_id=?1 ORDER BY id DESC LIMIT ?2"
+    ))?;
+    let mut rows = stmt.query(rusqlite::params![webhook_id, limit])?;
+    let mut deliveries = Vec::new();
+    while let Some(row) = rows.next()? {
+        deliveries.push(delivery_from_row(row)?);
+    }
+    Ok(Some(deliveries))
+}
@@ -27,6 +27,11 @@ pub(crate) enum ErrorType {
     Forbidden,
     Cryptography,
     NotFound,
+    Conflict,
+    /// The database is busy or has no free connection. The request can be retried.
+    Unavailable,
+    /// The entity changed since the client read it.
+    PreconditionFailed,
 }
 
 impl Error {
@@ -67,7 +72,12 @@ impl From<&'static str> for Error {
 
 impl From<rusqlite::Error> for Error {
     fn from(value: rusqlite::Error) -> Self {
-        Self::new(value.to_string())
+        match value.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => {
+                Self(value.to_string(), ErrorType::Unavailable)
+            }
+            _ => Self::new(value.to_string()),
+        }
     }
 }
 
@@ -82,6 +92,9 @@ impl response::IntoResponse for Error {
             ErrorType::Runtime | ErrorType::Cryptography => StatusCode::BAD_REQUEST,
             ErrorType::Forbidden => StatusCode::FORBIDDEN,
             ErrorType::NotFound => StatusCode::NOT_FOUND,
+            ErrorType::Conflict => StatusCode::CONFLICT,
+            ErrorType::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorType::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
         };
         Response::builder()
             .status(status_code)
@@ -0,0 +1,494 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::models::{AbbreviatedAbility, Item};
+use serde::Deserialize;
+use serde_json::Value;
+
+const ABILITY_TYPES: [&str; 4] = [
+    "GenericAbilityGameData",
+    "GenericSpellGameData",
+    "GenericCipherAbilityGameData",
+    "PhraseGameData",
+];
+const ITEM_TYPES: [&str; 5] = [
+    "EquippableGameData",
+    "WeaponGameData",
+    "ShieldGameData",
+    "ArmorGameData",
+    "ConsumableGameData",
+];
+/// The enchantments of the items, whose names are in the `itemmods` string table.
+const ITEM_MOD_TYPE: &str = "ItemModGameData";
+/// Objects whose debug name is the name of a tag, e.g. `Stunned` or `Wizard`.
+const NAMED_TAG_TYPES: [&str; 2] = ["AfflictionGameData", "CharacterClassGameData"];
+/// Status effect types that correspond to a tag.
+const STATUS_EFFECT_TAGS: [(&str, &str); 16] = [
+    ("AccuracyBonus", "mod_accuracy"),
+    ("DeflectionBonus", "mod_deflection"),
+    ("ReflexBonus", "mod_reflex"),
+    ("FortitudeBonus", "mod_fortitude"),
+    ("WillBonus", "mod_will"),
+    ("MoveRateMult", "mod_move_speed"),
+    ("StrideBonus", "mod_stride"),
+    ("ActionSpeedMult", "mod_action_speed"),
+    ("RecoveryTimeMult", "mod_recovery_time"),
+    ("ReloadTimeMult", "mod_reload_time"),
+    ("PenetrationBonus", "mod_penetration"),
+    ("MaxHealthBonus", "mod_max_health"),
+    ("DamageMult", "mod_damage"),
+    ("Invisible", "invisible"),
+    ("KnockedDown", "prone"),
+    ("Pull", "pull"),
+];
+/// How many references away from the ability or item the tags are still looked for.
+const MAX_REFERENCE_DEPTH: usize = 4;
+const EMPTY_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+#[derive(Debug, Deserialize)]
+struct Bundle {
+    #[serde(rename = "GameDataObjects")]
+    objects: Vec<GameDataObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameDataObject {
+    #[serde(rename = "$type")]
+    type_name: String,
+    #[serde(rename = "DebugName", default)]
+    debug_name: String,
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(rename = "Components", default)]
+    components: Vec<Value>,
+}
+
+impl GameDataObject {
+    /// `Game.GameData.WeaponGameData, Assembly-CSharp` becomes `WeaponGameData`.
+    fn short_type(&self) -> &str {
+        let type_name = self.type_name.split(',').next().unwrap_or_default();
+        type_name.rsplit('.').next().unwrap_or_default()
+    }
+
+    fn display_name(&self) -> Option<i64> {
+        self.components
+            .iter()
+            .find_map(|component| component.get("DisplayName")?.as_i64())
+    }
+}
+
+/// Entities read from the game data, each with the ID of the game data object.
+#[derive(Debug, Default)]
+pub(super) struct GameData {
+    pub(super) items: Vec<(String, Item)>,
+    pub(super) abilities: Vec<(String, AbbreviatedAbility)>,
+}
+
+/// Lists the files with the extension under the directory, sorted by path.
+pub(super) fn find_files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|err| format!("Failed to read the directory {}: {err:?}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == extension) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn parse_string_table(contents: &str) -> Result<HashMap<i64, String>, Error> {
+    let document = roxmltree::Document::parse(contents)
+        .map_err(|err| format!("Failed to parse the string table: {err}"))?;
+    let child_text = |entry: roxmltree::Node, name: &str| {
+        entry
+            .children()
+            .find(|child| child.has_tag_name(name))
+            .and_then(|child| child.text())
+            .map(str::to_string)
+    };
+    Ok(document
+        .descendants()
+        .filter(|node| node.has_tag_name("Entry"))
+        .filter_map(|entry| {
+            let id = child_text(entry, "ID")?.trim().parse().ok()?;
+            Some((id, child_text(entry, "DefaultText")?))
+        })
+        .collect())
+}
+
+/// Reads the string tables by their name, e.g. `abilities` or `items`. When the game data
+/// holds several languages, the English tables win.
+fn read_string_tables(dir: &Path) -> Result<HashMap<String, HashMap<i64, String>>, Error> {
+    let mut tables = HashMap::new();
+    for path in find_files(dir, "stringtable")? {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let english = path
+            .components()
+            .any(|component| component.as_os_str() == "en");
+        if tables.contains_key(name) && !english {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {err:?}", path.display()))?;
+        tables.insert(name.to_string(), parse_string_table(&contents)?);
+    }
+    Ok(tables)
+}
+
+fn parse_bundle(contents: &str) -> Result<Vec<GameDataObject>, Error> {
+    // The game writes the bundles with a byte order mark
+    let contents = contents.trim_start_matches('\u{feff}');
+    serde_json::from_str::<Bundle>(contents)
+        .map(|bundle| bundle.objects)
+        .map_err(|err| format!("Failed to parse the game data bundle: {err:?}").into())
+}
+
+/// Collects the IDs of the game data objects the value references.
+fn referenced_ids(value: &Value, ids: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                match field {
+                    Value::String(id) if key.ends_with("ID") && id != EMPTY_ID => {
+                        ids.push(id.clone())
+                    }
+                    Value::Array(values) if key.ends_with("IDs") => ids.extend(
+                        values
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .filter(|id| *id != EMPTY_ID)
+                            .map(str::to_string),
+                    ),
+                    _ => referenced_ids(field, ids),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| referenced_ids(value, ids)),
+        _ => {}
+    }
+}
+
+/// Collects the values of the fields with the key, e.g. every `DamageType`.
+fn field_values<'a>(value: &'a Value, key: &str, values: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                match field.as_str() {
+                    Some(text) if name == key => values.push(text),
+                    _ => field_values(field, key, values),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| field_values(item, key, values)),
+        _ => {}
+    }
+}
+
+/// `MightAffliction` becomes `might_affliction`.
+fn to_tag(name: &str) -> String {
+    let mut tag = String::new();
+    for (index, char) in name.chars().enumerate() {
+        if char.is_uppercase() && index > 0 && !tag.ends_with('_') {
+            tag.push('_');
+        }
+        match char {
+            ' ' | '-' => tag.push('_'),
+            _ => tag.extend(char.to_lowercase()),
+        }
+    }
+    tag
+}
+
+/// Names of the tags the object hints at, before they are checked against the vocabulary.
+fn tag_hints(object: &GameDataObject) -> Vec<String> {
+    let mut hints = Vec::new();
+    if NAMED_TAG_TYPES.contains(&object.short_type()) {
+        hints.push(to_tag(&object.debug_name));
+    }
+    for component in &object.components {
+        let mut values = Vec::new();
+        field_values(component, "DamageType", &mut values);
+        hints.extend(values.into_iter().map(to_tag));
+
+        let mut values = Vec::new();
+        field_values(component, "StatusEffectType", &mut values);
+        hints.extend(values.into_iter().filter_map(|value| {
+            STATUS_EFFECT_TAGS
+                .iter()
+                .find(|(effect, _)| *effect == value)
+                .map(|(_, tag)| tag.to_string())
+        }));
+    }
+    hints
+}
+
+/// Walks the references of the ability or item, e.g. its attacks, status effects and
+/// afflictions, and collects the tags of the vocabulary they hint at.
+fn find_tags(
+    root: &GameDataObject,
+    objects: &HashMap<&str, &GameDataObject>,
+    vocabulary: &HashSet<String>,
+) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut visited = HashSet::from([root.id.as_str()]);
+    let mut queue = VecDeque::from([(root, 0)]);
+    while let Some((object, depth)) = queue.pop_front() {
+        for tag in tag_hints(object) {
+            if vocabulary.contains(&tag) && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        if depth == MAX_REFERENCE_DEPTH {
+            continue;
+        }
+        let mut ids = Vec::new();
+        object
+            .components
+            .iter()
+            .for_each(|component| referenced_ids(component, &mut ids));
+        for id in ids {
+            let Some(referenced) = objects.get(id.as_str()) else {
+                continue;
+            };
+            // Other abilities and items have tags of their own
+            let entity_type = referenced.short_type();
+            if ABILITY_TYPES.contains(&entity_type) || ITEM_TYPES.contains(&entity_type) {
+                continue;
+            }
+            if visited.insert(referenced.id.as_str()) {
+                queue.push_back((referenced, depth + 1));
+            }
+        }
+    }
+    tags
+}
+
+/// Lists the names of the enchantments the item references in the format of the wiki data,
+/// e.g. `CURRENT ENCHANTMENTS:\n- Superb\n`, or an empty string if it has none.
+fn effects_description(
+    item: &GameDataObject,
+    objects: &HashMap<&str, &GameDataObject>,
+    display_name: impl Fn(&GameDataObject, &str) -> Option<String>,
+) -> String {
+    let mut ids = Vec::new();
+    item.components
+        .iter()
+        .for_each(|component| referenced_ids(component, &mut ids));
+    let mut enchantments: Vec<String> = Vec::new();
+    for id in ids {
+        let Some(item_mod) = objects.get(id.as_str()) else {
+            continue;
+        };
+        if item_mod.short_type() != ITEM_MOD_TYPE {
+            continue;
+        }
+        if let Some(name) = display_name(item_mod, "itemmods").filter(|name| !name.is_empty())
+            && !enchantments.contains(&name)
+        {
+            enchantments.push(name);
+        }
+    }
+    if enchantments.is_empty() {
+        return String::new();
+    }
+    let mut description = "CURRENT ENCHANTMENTS:\n".to_string();
+    for enchantment in enchantments {
+        description.push_str(&format!("- {enchantment}\n"));
+    }
+    description
+}
+
+fn map_objects(
+    objects: &[GameDataObject],
+    strings: &HashMap<String, HashMap<i64, String>>,
+    vocabulary: &HashSet<String>,
+) -> GameData {
+    let by_id: HashMap<&str, &GameDataObject> = objects
+        .iter()
+        .map(|object| (object.id.as_str(), object))
+        .collect();
+    let display_name = |object: &GameDataObject, table: &str| {
+        let id = object.display_name()?;
+        strings
+            .get(table)?
+            .get(&id)
+            .map(|name| name.trim().to_string())
+    };
+
+    let mut game_data = GameData::default();
+    let mut slugs = HashSet::new();
+    for object in objects {
+        let entity_type = object.short_type();
+        if ABILITY_TYPES.contains(&entity_type) {
+            let Some(name) = display_name(object, "abilities").filter(|name| !name.is_empty())
+            else {
+                continue;
+            };
+            let slug = slug::slugify(&name);
+            if !slugs.insert(slug.clone()) {
+                continue;
+            }
+            let ability = AbbreviatedAbility {
+                name,
+                slug,
+                tags: find_tags(object, &by_id, vocabulary),
+                wiki_url: String::new(),
+            };
+            game_data.abilities.push((object.id.clone(), ability));
+        } else if ITEM_TYPES.contains(&entity_type) {
+            let Some(name) = display_name(object, "items").filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            let slug = slug::slugify(&name);
+            if !slugs.insert(slug.clone()) {
+                continue;
+            }
+            let item = Item {
+                name,
+                slug,
+                wiki_url: String::new(),
+                tags: find_tags(object, &by_id, vocabulary),
+                effects_description: effects_description(object, &by_id, display_name),
+            };
+            game_data.items.push((object.id.clone(), item));
+        }
+    }
+    game_data
+}
+
+/// Reads the `.gamedatabundle` and `.stringtable` files under the directory and maps the
+/// abilities and items onto hammer's entities. Only tags of the vocabulary are kept, and the
+/// effects of an item are the names of its enchantments.
+pub(super) fn read_game_data(dir: &Path, vocabulary: &HashSet<String>) -> Result<GameData, Error> {
+    let strings = read_string_tables(dir)?;
+    let mut objects = Vec::new();
+    for path in find_files(dir, "gamedatabundle")? {
+        tracing::trace!("game data bundle: {path:?}");
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {err:?}", path.display()))?;
+        objects.extend(
+            parse_bundle(&contents).map_err(|err| format!("{}: {}", path.display(), err.0))?,
+        );
+    }
+    Ok(map_objects(&objects, &strings, vocabulary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLE: &str = r#"{"GameDataObjects": [
+        {
+            "$type": "Game.GameData.GenericSpellGameData, Assembly-CSharp",
+            "DebugName": "Fireball",
+            "ID": "spell-1",
+            "Components": [{
+                "$type": "Game.GameData.GenericAbilityComponent, Assembly-CSharp",
+                "DisplayName": 10,
+                "AbilityClassID": "class-1",
+                "StatusEffectsIDs": ["effect-1"]
+            }]
+        },
+        {
+            "$type": "Game.GameData.CharacterClassGameData, Assembly-CSharp",
+            "DebugName": "Wizard",
+            "ID": "class-1",
+            "Components": []
+        },
+        {
+            "$type": "Game.GameData.StatusEffectGameData, Assembly-CSharp",
+            "DebugName": "Fireball_Burn",
+            "ID": "effect-1",
+            "Components": [{
+                "StatusEffectType": "AccuracyBonus",
+                "AfflictionTypeID": "affliction-1",
+                "DamageData": { "DamageType": "Burn" }
+            }]
+        },
+        {
+            "$type": "Game.GameData.AfflictionGameData, Assembly-CSharp",
+            "DebugName": "Staggered",
+            "ID": "affliction-1",
+            "Components": []
+        },
+        {
+            "$type": "Game.GameData.WeaponGameData, Assembly-CSharp",
+            "DebugName": "Unnamed",
+            "ID": "weapon-1",
+            "Components": [{ "DisplayName": 99 }]
+        },
+        {
+            "$type": "Game.GameData.WeaponGameData, Assembly-CSharp",
+            "DebugName": "Sabre_Frostseeker",
+            "ID": "weapon-2",
+            "Components": [{ "DisplayName": 20, "ItemModsIDs": ["mod-1", "mod-2"] }]
+        },
+        {
+            "$type": "Game.GameData.ItemModGameData, Assembly-CSharp",
+            "DebugName": "IM_Freezing_Lash",
+            "ID": "mod-1",
+            "Components": [{ "DisplayName": 30 }]
+        },
+        {
+            "$type": "Game.GameData.ItemModGameData, Assembly-CSharp",
+            "DebugName": "IM_Hidden",
+            "ID": "mod-2",
+            "Components": [{ "DisplayName": 98 }]
+        }
+    ]}"#;
+
+    const STRINGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+        <StringTableFile>
+          <Entries>
+            <Entry><ID>10</ID><DefaultText>Fireball</DefaultText><FemaleText /></Entry>
+            <Entry><ID>20</ID><DefaultText>Frostseeker</DefaultText><FemaleText /></Entry>
+            <Entry><ID>30</ID><DefaultText>Freezing Lash</DefaultText><FemaleText /></Entry>
+          </Entries>
+        </StringTableFile>"#;
+
+    #[test]
+    fn test_map_objects() {
+        let objects = parse_bundle(&format!("\u{feff}{BUNDLE}")).unwrap();
+        let strings = ["abilities", "items", "itemmods"]
+            .map(|table| (table.to_string(), parse_string_table(STRINGS).unwrap()))
+            .into();
+        let vocabulary = ["wizard", "burn", "staggered", "mod_accuracy"]
+            .map(str::to_string)
+            .into();
+
+        let game_data = map_objects(&objects, &strings, &vocabulary);
+
+        let (id, item) = &game_data.items[0];
+        assert_eq!(id, "weapon-2");
+        assert_eq!(item.slug, "frostseeker");
+        assert_eq!(
+            item.effects_description,
+            "CURRENT ENCHANTMENTS:\n- Freezing Lash\n"
+        );
+        let (id, ability) = &game_data.abilities[0];
+        assert_eq!(id, "spell-1");
+        assert_eq!(ability.slug, "fireball");
+        let mut tags = ability.tags.clone();
+        tags.sort();
+        assert_eq!(tags, vec!["burn", "mod_accuracy", "staggered", "wizard"]);
+    }
+
+    #[test]
+    fn test_to_tag() {
+        assert_eq!(to_tag("MightAffliction"), "might_affliction");
+        assert_eq!(to_tag("Stunned"), "stunned");
+    }
+}
@@ -1,24 +1,800 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::Path;
+
 use crate::db;
 use crate::error::Error;
+use crate::models::{
+    AbbreviatedAbility, CONFIG, ChangeAction, ChangeEvent, EntityTag, ImportRunChange,
+    IndexedEntityType, Item, SyncCounts, TagSource, changed_fields,
+};
+use crate::resources::{self, Resource};
+use crate::webhooks;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
+use super::game_data::{self, read_game_data};
+use super::merge::{self, Conflict, CurrentState};
 use super::read_abilities::read_abilities;
+use super::read_csv::{self, read_abilities_csv, read_items_csv};
 use super::read_items::read_items;
+use super::tags::{TagSyncSummary, VocabularyTag, read_vocabulary, sync_tags_in_transaction};
+
+/// The quarry output the import reads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum InputFormat {
+    /// The post-processed JSON files with tags and effects.
+    #[default]
+    Json,
+    /// The CSV exports of the scrapers. They only refresh names and wiki URLs.
+    Csv,
+    /// The `.gamedatabundle` files of the game. They only add tags to the entities they
+    /// are reconciled with and record the game data IDs.
+    GameData,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SyncOptions {
+    pub(crate) format: InputFormat,
+    /// Computes the summary without writing anything to the database.
+    pub(crate) dry_run: bool,
+    /// Moves entities that are missing from the quarry output to the trash.
+    pub(crate) remove_missing: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SyncSummary {
+    pub(crate) run_id: i64,
+    pub(crate) items: SyncCounts,
+    pub(crate) abilities: SyncCounts,
+    pub(crate) tags: TagSyncSummary,
+    pub(crate) conflicts: usize,
+    /// Records skipped because they did not match the JSON Schema.
+    pub(crate) invalid: usize,
+    /// Game data records that matched no entity.
+    pub(crate) unmatched: usize,
+    pub(crate) dry_run: bool,
+}
+
+impl Display for SyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dry_run {
+            writeln!(f, "Dry run, nothing was written to the database")?;
+        } else {
+            writeln!(f, "Import run {}", self.run_id)?;
+        }
+        writeln!(f, "Tags: {}", self.tags)?;
+        writeln!(f, "Items: {}", self.items)?;
+        writeln!(f, "Abilities: {}", self.abilities)?;
+        writeln!(f, "Conflicts: {}", self.conflicts)?;
+        if self.unmatched > 0 {
+            writeln!(f, "Unmatched game data records: {}", self.unmatched)?;
+        }
+        write!(f, "Invalid records skipped: {}", self.invalid)
+    }
+}
+
+enum SyncOutcome {
+    Created {
+        after: String,
+    },
+    Updated {
+        before: String,
+        after: String,
+    },
+    Unchanged,
+    /// A game data record without an entity to reconcile with.
+    Unmatched,
+}
+
+fn count(counts: &mut SyncCounts, outcome: &SyncOutcome) {
+    match outcome {
+        SyncOutcome::Created { .. } => counts.created += 1,
+        SyncOutcome::Updated { .. } => counts.updated += 1,
+        SyncOutcome::Unchanged => counts.unchanged += 1,
+        SyncOutcome::Unmatched => {}
+    }
+}
+
+/// Records the change in the import run so that it can be rolled back.
+fn record_change(
+    run_id: i64,
+    entity_type: IndexedEntityType,
+    slug: &str,
+    outcome: SyncOutcome,
+    tx: &Transaction,
+) -> Result<(), Error> {
+    let (action, before, after) = match outcome {
+        SyncOutcome::Created { after } => (ChangeAction::Created, None, Some(after)),
+        SyncOutcome::Updated { before, after } => {
+            (ChangeAction::Updated, Some(before), Some(after))
+        }
+        SyncOutcome::Unchanged | SyncOutcome::Unmatched => return Ok(()),
+    };
+    let change = ImportRunChange {
+        entity_type,
+        slug: slug.to_string(),
+        action,
+        before,
+        after,
+    };
+    insert_change(run_id, change, tx)
+}
+
+/// Records the change in the import run and queues its deliveries to the webhooks, which the
+/// server sends once the import is committed.
+fn insert_change(run_id: i64, change: ImportRunChange, tx: &Transaction) -> Result<(), Error> {
+    db::import_run::insert_change(run_id, &change, tx)?;
+    let fields = match (&change.before, &change.after) {
+        (Some(before), Some(after)) => changed_fields(&parse_json(before)?, &parse_json(after)?)?,
+        _ => vec![],
+    };
+    let event = ChangeEvent {
+        entity_type: change.entity_type,
+        slug: change.slug,
+        action: change.action,
+        fields,
+        author: "cli".to_string(),
+    };
+    webhooks::enqueue(&event, tx)?;
+    Ok(())
+}
+
+fn parse_json(snapshot: &str) -> Result<serde_json::Value, Error> {
+    serde_json::from_str(snapshot)
+        .map_err(|err| format!("Failed to parse the entity snapshot: {err:?}").into())
+}
+
+fn snapshot<T: Serialize>(entity: &T) -> Result<String, Error> {
+    serde_json::to_string(entity)
+        .map_err(|err| format!("Failed to serialize the entity snapshot: {err:?}").into())
+}
+
+/// Hashes the contents of the files, in order.
+fn files_hash<P: AsRef<std::path::Path>>(paths: &[P]) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    for path in paths {
+        hasher.update(std::fs::read(path)?);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
 
-pub(crate) fn import_to_db() -> Result<(), Error> {
-    // Load items and abilities from JSON
-    let items = read_items().inspect_err(|err| tracing::warn!("Failed to read items. {err:?}"))?;
-    let abilities =
+/// Hashes the file at the path, or the embedded copy of the resource the import read instead.
+fn resource_hash(path: &str, resource: Resource) -> Result<String, Error> {
+    let contents = resources::read_to_string(path, resource)?;
+    Ok(format!("sha256:{:x}", Sha256::digest(contents)))
+}
+
+/// The tags, the records to import and the paths and hashes of the files they come from.
+#[derive(Default)]
+struct QuarryInput {
+    vocabulary: Vec<VocabularyTag>,
+    items: Vec<Item>,
+    abilities: Vec<AbbreviatedAbility>,
+    invalid: usize,
+    /// Game data IDs of the records, by slug.
+    item_game_data_ids: HashMap<String, String>,
+    ability_game_data_ids: HashMap<String, String>,
+    items_source: (String, String),
+    abilities_source: (String, String),
+}
+
+fn read_json_input() -> Result<QuarryInput, Error> {
+    let (items, invalid_items) =
+        read_items().inspect_err(|err| tracing::warn!("Failed to read items. {err:?}"))?;
+    let (abilities, invalid_abilities) =
         read_abilities().inspect_err(|err| tracing::warn!("Failed to read abilities. {err:?}"))?;
+    let items_path = &CONFIG.items_path;
+    let abilities_path = &CONFIG.abilities_path;
+    Ok(QuarryInput {
+        vocabulary: read_vocabulary()?,
+        items,
+        abilities,
+        invalid: invalid_items + invalid_abilities,
+        items_source: (
+            items_path.clone(),
+            resource_hash(items_path, Resource::Items)?,
+        ),
+        abilities_source: (
+            abilities_path.clone(),
+            resource_hash(abilities_path, Resource::Abilities)?,
+        ),
+        ..Default::default()
+    })
+}
 
+fn read_csv_input() -> Result<QuarryInput, Error> {
+    let items =
+        read_items_csv().inspect_err(|err| tracing::warn!("Failed to read items. {err:?}"))?;
+    let abilities = read_abilities_csv()
+        .inspect_err(|err| tracing::warn!("Failed to read abilities. {err:?}"))?;
+    let items_path = read_csv::csv_path(read_csv::ITEMS_FILE);
+    let abilities_paths = read_csv::ABILITY_FILES.map(read_csv::csv_path);
+    Ok(QuarryInput {
+        vocabulary: read_vocabulary()?,
+        items,
+        abilities,
+        invalid: 0,
+        items_source: (
+            items_path.display().to_string(),
+            files_hash(&[&items_path])?,
+        ),
+        abilities_source: (
+            abilities_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            files_hash(&abilities_paths)?,
+        ),
+        ..Default::default()
+    })
+}
+
+fn read_game_data_input() -> Result<QuarryInput, Error> {
+    let dir = CONFIG
+        .game_data_dir
+        .as_deref()
+        .ok_or("Set game_data_dir to import the game data")?;
+    let vocabulary = read_vocabulary()?;
+    let names: HashSet<String> = vocabulary.iter().map(|tag| tag.name.clone()).collect();
+    let game_data = read_game_data(Path::new(dir), &names)
+        .inspect_err(|err| tracing::warn!("Failed to read the game data. {err:?}"))?;
+    let source = (
+        dir.to_string(),
+        files_hash(&game_data::find_files(Path::new(dir), "gamedatabundle")?)?,
+    );
+    let (item_game_data_ids, items) = game_data
+        .items
+        .into_iter()
+        .map(|(id, item)| ((item.slug.clone(), id), item))
+        .unzip();
+    let (ability_game_data_ids, abilities) = game_data
+        .abilities
+        .into_iter()
+        .map(|(id, ability)| ((ability.slug.clone(), id), ability))
+        .unzip();
+    Ok(QuarryInput {
+        vocabulary,
+        items,
+        abilities,
+        item_game_data_ids,
+        ability_game_data_ids,
+        items_source: source.clone(),
+        abilities_source: source,
+        ..Default::default()
+    })
+}
+
+/// Synchronizes the database with the quarry output in a single transaction.
+///
+/// Entities are matched by slug. New entities are inserted and existing ones
+/// are updated, except for the fields editors have locked and the tags humans
+/// have added. Those changes are written to the conflict report instead.
+/// The run and every change it makes are recorded so that it can be rolled back.
+pub(crate) fn import_to_db(options: SyncOptions) -> Result<SyncSummary, Error> {
+    let input = match options.format {
+        InputFormat::Json => read_json_input()?,
+        InputFormat::Csv => read_csv_input()?,
+        InputFormat::GameData => read_game_data_input()?,
+    };
     let mut conn = db::get_connection()?;
+    let (summary, conflicts) = sync(input, options, &mut conn)?;
+    if !options.dry_run {
+        write_conflict_report(&conflicts)?;
+    }
+    Ok(summary)
+}
 
-    // Insert items and abilities
-    for item in items {
-        db::item::insert(&item, &mut conn)?;
+/// Runs the sync in a transaction that is only committed if it is not a dry run.
+fn sync(
+    input: QuarryInput,
+    options: SyncOptions,
+    conn: &mut Connection,
+) -> Result<(SyncSummary, Vec<Conflict>), Error> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let result = sync_in_transaction(input, options, &tx)?;
+    if options.dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
     }
-    for ability in abilities {
-        db::ability::insert_abbreviated_ability(&ability)?;
+    Ok(result)
+}
+
+/// Synchronizes the tags, items and abilities with the input as part of the transaction.
+/// Returns the summary and the changes that could not be applied.
+fn sync_in_transaction(
+    input: QuarryInput,
+    options: SyncOptions,
+    tx: &Transaction,
+) -> Result<(SyncSummary, Vec<Conflict>), Error> {
+    let QuarryInput {
+        vocabulary,
+        items,
+        abilities,
+        invalid,
+        item_game_data_ids,
+        ability_game_data_ids,
+        items_source,
+        abilities_source,
+    } = input;
+    let run_id = db::import_run::insert(
+        (&items_source.0, &items_source.1),
+        (&abilities_source.0, &abilities_source.1),
+        tx,
+    )?;
+    let source = TagSource::Import(run_id.to_string());
+    let mut summary = SyncSummary {
+        run_id,
+        invalid,
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    let mut conflicts = Vec::new();
+    // Skipped records are missing from the output too, but they should not be removed.
+    // The game data has no wiki pages, so it is not a complete list of the entities either.
+    let remove_missing =
+        options.remove_missing && summary.invalid == 0 && options.format != InputFormat::GameData;
+    if options.remove_missing && !remove_missing {
+        tracing::warn!(
+            "Not removing missing entities because some records were invalid or come from the game data"
+        );
     }
 
+    // Tags, so that the items and abilities can reference new ones
+    summary.tags = sync_tags_in_transaction(&vocabulary, tx)?;
+
+    // Items
+    let deleted_items: Vec<String> = db::item::find_deleted(tx)?
+        .into_iter()
+        .map(|item| item.slug)
+        .collect();
+    let item_slugs: Vec<String> = items.iter().map(|item| item.slug.clone()).collect();
+    for mut item in items {
+        let game_data_id = item_game_data_ids.get(&item.slug);
+        // Entities keep their game data ID when the wiki renames them
+        if let Some(id) = game_data_id
+            && let Some(slug) = db::item::find_slug_by_game_data_id(id, tx)?
+        {
+            item.slug = slug;
+        }
+        if deleted_items.contains(&item.slug) {
+            conflicts.push(merge::deleted_conflict(IndexedEntityType::Item, &item.slug));
+            count(&mut summary.items, &SyncOutcome::Unchanged);
+            continue;
+        }
+        let slug = item.slug.clone();
+        let outcome = sync_item(item, options.format, &source, &mut conflicts, tx)?;
+        if matches!(outcome, SyncOutcome::Unmatched) {
+            summary.unmatched += 1;
+            continue;
+        }
+        if let Some(id) = game_data_id {
+            db::item::set_game_data_id(&slug, id, tx)?;
+        }
+        count(&mut summary.items, &outcome);
+        record_change(run_id, IndexedEntityType::Item, &slug, outcome, tx)?;
+    }
+    if remove_missing {
+        for item in db::item::find_all(tx)? {
+            if !item_slugs.contains(&item.slug) {
+                tracing::debug!(
+                    "Removing the item missing from the quarry output: {}",
+                    item.slug
+                );
+                db::item::delete(&item.slug, tx)?;
+                let change = ImportRunChange {
+                    entity_type: IndexedEntityType::Item,
+                    slug: item.slug.clone(),
+                    action: ChangeAction::Removed,
+                    before: Some(snapshot(&Item::from(item))?),
+                    after: None,
+                };
+                insert_change(run_id, change, tx)?;
+                summary.items.removed += 1;
+            }
+        }
+    }
+
+    // Abilities
+    let deleted_abilities: Vec<String> = db::ability::find_deleted(tx)?
+        .into_iter()
+        .map(|ability| ability.slug)
+        .collect();
+    let ability_slugs: Vec<String> = abilities
+        .iter()
+        .map(|ability| ability.slug.clone())
+        .collect();
+    for mut ability in abilities {
+        let game_data_id = ability_game_data_ids.get(&ability.slug);
+        if let Some(id) = game_data_id
+            && let Some(slug) = db::ability::find_slug_by_game_data_id(id, tx)?
+        {
+            ability.slug = slug;
+        }
+        if deleted_abilities.contains(&ability.slug) {
+            conflicts.push(merge::deleted_conflict(
+                IndexedEntityType::Ability,
+                &ability.slug,
+            ));
+            count(&mut summary.abilities, &SyncOutcome::Unchanged);
+            continue;
+        }
+        let slug = ability.slug.clone();
+        let outcome = sync_ability(ability, options.format, &source, &mut conflicts, tx)?;
+        if matches!(outcome, SyncOutcome::Unmatched) {
+            summary.unmatched += 1;
+            continue;
+        }
+        if let Some(id) = game_data_id {
+            db::ability::set_game_data_id(&slug, id, tx)?;
+        }
+        count(&mut summary.abilities, &outcome);
+        record_change(run_id, IndexedEntityType::Ability, &slug, outcome, tx)?;
+    }
+    if remove_missing {
+        for ability in db::ability::find_all(tx)? {
+            if !ability_slugs.contains(&ability.slug) {
+                tracing::debug!(
+                    "Removing the ability missing from the quarry output: {}",
+                    ability.slug
+                );
+                db::ability::delete(&ability.slug, tx)?;
+                let change = ImportRunChange {
+                    entity_type: IndexedEntityType::Ability,
+                    slug: ability.slug.clone(),
+                    action: ChangeAction::Removed,
+                    before: Some(snapshot(&AbbreviatedAbility::from(ability))?),
+                    after: None,
+                };
+                insert_change(run_id, change, tx)?;
+                summary.abilities.removed += 1;
+            }
+        }
+    }
+
+    summary.conflicts = conflicts.len();
+    db::import_run::finish(
+        run_id,
+        &summary.items,
+        &summary.abilities,
+        summary.conflicts,
+        tx,
+    )?;
+    Ok((summary, conflicts))
+}
+
+/// The CSV exports only bring names and wiki URLs, the game data only brings tags.
+/// The rest of the incoming item comes from the current one.
+fn sync_item(
+    item: Item,
+    format: InputFormat,
+    source: &TagSource,
+    conflicts: &mut Vec<Conflict>,
+    tx: &Transaction,
+) -> Result<SyncOutcome, Error> {
+    let Some(current) = db::item::find_by_slug(&item.slug, tx)? else {
+        if format == InputFormat::GameData {
+            return Ok(SyncOutcome::Unmatched);
+        }
+        db::item::insert_in_transaction(&item, source, tx)?;
+        return Ok(SyncOutcome::Created {
+            after: snapshot(&item)?,
+        });
+    };
+    let item = match format {
+        InputFormat::Json => item,
+        InputFormat::Csv => Item {
+            tags: current.tags.clone(),
+            effects_description: current.effects_description.clone(),
+            ..item
+        },
+        InputFormat::GameData => Item {
+            tags: with_tags(&current.tags, item.tags),
+            ..Item::from(current.clone())
+        },
+    };
+    let locks = db::item::find_locks(&current.slug, tx)?;
+    let human_tags = find_human_tags(db::item::find_tag_sources(&current.slug, tx)?);
+    let state = CurrentState {
+        entity: &current,
+        locks: &locks,
+        human_tags: &human_tags,
+    };
+    let (merged, item_conflicts) = merge::merge_item(state, item);
+    conflicts.extend(item_conflicts);
+    if merged.name == current.name
+        && merged.wiki_url == current.wiki_url
+        && merged.effects_description == current.effects_description
+        && merge::same_tags(&merged.tags, &current.tags)
+    {
+        return Ok(SyncOutcome::Unchanged);
+    }
+    db::item::update_in_transaction(&current.slug, &merged, source, tx)?;
+    Ok(SyncOutcome::Updated {
+        before: snapshot(&Item::from(current))?,
+        after: snapshot(&merged)?,
+    })
+}
+
+/// The CSV exports only bring names and wiki URLs, the game data only brings tags.
+/// The rest of the incoming ability comes from the current one.
+fn sync_ability(
+    ability: AbbreviatedAbility,
+    format: InputFormat,
+    source: &TagSource,
+    conflicts: &mut Vec<Conflict>,
+    tx: &Transaction,
+) -> Result<SyncOutcome, Error> {
+    let Some(current) = db::ability::find_by_slug(&ability.slug, tx)? else {
+        if format == InputFormat::GameData {
+            return Ok(SyncOutcome::Unmatched);
+        }
+        db::ability::insert_abbreviated_ability(&ability, source, tx)?;
+        return Ok(SyncOutcome::Created {
+            after: snapshot(&ability)?,
+        });
+    };
+    let ability = match format {
+        InputFormat::Json => ability,
+        InputFormat::Csv => AbbreviatedAbility {
+            tags: current.tags.clone(),
+            ..ability
+        },
+        InputFormat::GameData => AbbreviatedAbility {
+            tags: with_tags(&current.tags, ability.tags),
+            ..AbbreviatedAbility::from(current.clone())
+        },
+    };
+    let locks = db::ability::find_locks(&current.slug, tx)?;
+    let human_tags = find_human_tags(db::ability::find_tag_sources(&current.slug, tx)?);
+    let state = CurrentState {
+        entity: &current,
+        locks: &locks,
+        human_tags: &human_tags,
+    };
+    let (merged, ability_conflicts) = merge::merge_ability(state, ability);
+    conflicts.extend(ability_conflicts);
+    if merged.name == current.name
+        && merged.wiki_url == current.wiki_url
+        && merge::same_tags(&merged.tags, &current.tags)
+    {
+        return Ok(SyncOutcome::Unchanged);
+    }
+    let after = snapshot(&merged)?;
+    db::ability::update_abbreviated_ability_in_transaction(&current.slug, merged, source, tx)?;
+    Ok(SyncOutcome::Updated {
+        before: snapshot(&AbbreviatedAbility::from(current))?,
+        after,
+    })
+}
+
+/// The current tags followed by the new ones.
+fn with_tags(current: &[String], new: Vec<String>) -> Vec<String> {
+    let mut tags = current.to_vec();
+    tags.extend(new.into_iter().filter(|tag| !current.contains(tag)));
+    tags
+}
+
+fn find_human_tags(tags: Vec<EntityTag>) -> Vec<String> {
+    tags.into_iter()
+        .filter(|tag| tag.source != "import")
+        .map(|tag| tag.name)
+        .collect()
+}
+
+fn write_conflict_report(conflicts: &[Conflict]) -> Result<(), Error> {
+    let path = &CONFIG.import_conflicts_path;
+    let report = serde_json::to_string_pretty(conflicts)
+        .map_err(|err| format!("Failed to serialize the conflict report: {err:?}"))?;
+    std::fs::write(path, report)?;
+    if conflicts.is_empty() {
+        tracing::info!("The import finished without conflicts");
+    } else {
+        tracing::warn!(
+            "The import left {} conflicts for manual resolution. See {path}",
+            conflicts.len()
+        );
+    }
     Ok(())
 }
+
+/// Imports the records as if the quarry output held them, without a tag vocabulary.
+#[cfg(test)]
+pub(super) fn sync_records(
+    items: Vec<Item>,
+    abilities: Vec<AbbreviatedAbility>,
+    options: SyncOptions,
+    conn: &mut Connection,
+) -> Result<SyncSummary, Error> {
+    let input = QuarryInput {
+        items,
+        abilities,
+        items_source: ("items.json".to_string(), "sha256:items".to_string()),
+        abilities_source: ("abilities.json".to_string(), "sha256:abilities".to_string()),
+        ..Default::default()
+    };
+    Ok(sync(input, options, conn)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TrashedEntity;
+
+    fn item(name: &str) -> Item {
+        Item {
+            name: name.to_string(),
+            slug: slug::slugify(name),
+            wiki_url: format!("https://example.com/{}", slug::slugify(name)),
+            tags: vec!["freeze".to_string()],
+            effects_description: String::new(),
+        }
+    }
+
+    fn ability(name: &str) -> AbbreviatedAbility {
+        AbbreviatedAbility {
+            name: name.to_string(),
+            slug: slug::slugify(name),
+            wiki_url: format!("https://example.com/{}", slug::slugify(name)),
+            tags: vec!["freeze".to_string()],
+        }
+    }
+
+    /// Created, updated, unchanged and removed.
+    fn counts(counts: &SyncCounts) -> [usize; 4] {
+        [
+            counts.created,
+            counts.updated,
+            counts.unchanged,
+            counts.removed,
+        ]
+    }
+
+    #[test]
+    fn test_a_repeated_import_leaves_everything_unchanged() {
+        let mut conn = db::get_test_connection();
+        let mut import = || {
+            sync_records(
+                vec![item("Drawn in Spring"), item("Torch")],
+                vec![ability("Frostfire")],
+                SyncOptions::default(),
+                &mut conn,
+            )
+            .unwrap()
+        };
+
+        let first = import();
+        assert_eq!(counts(&first.items), [2, 0, 0, 0]);
+        assert_eq!(counts(&first.abilities), [1, 0, 0, 0]);
+        assert_eq!(first.conflicts, 0);
+
+        let second = import();
+        assert_eq!(counts(&second.items), [0, 0, 2, 0]);
+        assert_eq!(counts(&second.abilities), [0, 0, 1, 0]);
+        assert_eq!(second.conflicts, 0);
+        assert!(
+            db::import_run::find_changes(second.run_id, &conn)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_a_dry_run_writes_nothing() {
+        let mut conn = db::get_test_connection();
+        let options = SyncOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        let summary = sync_records(
+            vec![item("Torch")],
+            vec![ability("Frostfire")],
+            options,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(counts(&summary.items), [1, 0, 0, 0]);
+        assert!(db::is_empty(&conn).unwrap());
+        assert!(db::import_run::find_all(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_missing_only_trashes_the_missing_entities() {
+        let mut conn = db::get_test_connection();
+        sync_records(
+            vec![item("Drawn in Spring"), item("Torch")],
+            vec![ability("Frostfire"), ability("Fan of Flames")],
+            SyncOptions::default(),
+            &mut conn,
+        )
+        .unwrap();
+        let options = SyncOptions {
+            remove_missing: true,
+            ..Default::default()
+        };
+
+        let summary = sync_records(
+            vec![item("Torch")],
+            vec![ability("Frostfire")],
+            options,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(counts(&summary.items), [0, 0, 1, 1]);
+        assert_eq!(counts(&summary.abilities), [0, 0, 1, 1]);
+        let slugs = |entities: Vec<TrashedEntity>| -> Vec<String> {
+            entities.into_iter().map(|entity| entity.slug).collect()
+        };
+        assert_eq!(
+            slugs(db::item::find_deleted(&conn).unwrap()),
+            ["drawn-in-spring"]
+        );
+        assert_eq!(
+            slugs(db::ability::find_deleted(&conn).unwrap()),
+            ["fan-of-flames"]
+        );
+        assert!(db::item::find_by_slug("torch", &conn).unwrap().is_some());
+        assert!(
+            db::ability::find_by_slug("frostfire", &conn)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_the_changes_of_an_import_are_queued_for_the_webhooks() {
+        let mut conn = db::get_test_connection();
+        let webhook = crate::models::NewWebhook {
+            url: "http://127.0.0.1:9/hook".to_string(),
+            secret: "s3cret".to_string(),
+        };
+        db::webhook::insert(&webhook, "admin@example.com", &conn).unwrap();
+        let mut import = |items: Vec<Item>, options: SyncOptions| {
+            sync_records(items, vec![], options, &mut conn).unwrap();
+        };
+        import(vec![item("Torch"), item("Lantern")], SyncOptions::default());
+        let dry_run = SyncOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        import(vec![item("Drawn in Spring")], dry_run);
+        let renamed = Item {
+            wiki_url: "https://example.com/renamed".to_string(),
+            ..item("Torch")
+        };
+        let remove_missing = SyncOptions {
+            remove_missing: true,
+            ..Default::default()
+        };
+        import(vec![renamed], remove_missing);
+
+        let events: Vec<(String, String, serde_json::Value)> = db::webhook::find_due(10, &conn)
+            .unwrap()
+            .into_iter()
+            .map(|delivery| {
+                let payload: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap();
+                (
+                    payload["slug"].as_str().unwrap().to_string(),
+                    payload["action"].as_str().unwrap().to_string(),
+                    payload["fields"].clone(),
+                )
+            })
+            .collect();
+        let event = |slug: &str, action: &str, fields: serde_json::Value| {
+            (slug.to_string(), action.to_string(), fields)
+        };
+        assert_eq!(
+            events,
+            [
+                event("torch", "created", serde_json::json!([])),
+                event("lantern", "created", serde_json::json!([])),
+                event("torch", "updated", serde_json::json!(["wiki_url"])),
+                event("lantern", "removed", serde_json::json!([])),
+            ]
+        );
+    }
+}
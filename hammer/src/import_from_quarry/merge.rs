@@ -0,0 +1,263 @@
+use crate::models::{
+    AbbreviatedAbility, IndexedEntityType, Item, PersistedAbbreviatedAbility, PersistedItem,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// A change from the import that was not applied automatically.
+#[derive(Debug, Serialize)]
+pub(super) struct Conflict {
+    #[serde(rename = "type")]
+    pub(super) entity_type: IndexedEntityType,
+    pub(super) slug: String,
+    pub(super) field: String,
+    pub(super) current: Value,
+    pub(super) incoming: Value,
+    pub(super) reason: ConflictReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(super) enum ConflictReason {
+    /// An editor locked the field.
+    #[serde(rename = "locked")]
+    Locked,
+    /// The import drops a tag that was added by an editor or an accepted proposal.
+    #[serde(rename = "human_tag")]
+    HumanTag,
+    /// The entity is in the trash.
+    #[serde(rename = "deleted")]
+    Deleted,
+}
+
+/// What is known about an entity in the database before merging the imported version into it.
+pub(super) struct CurrentState<'a, T> {
+    pub(super) entity: &'a T,
+    pub(super) locks: &'a [String],
+    pub(super) human_tags: &'a [String],
+}
+
+struct MergeContext<'a> {
+    entity_type: IndexedEntityType,
+    slug: &'a str,
+    locks: &'a [String],
+    conflicts: Vec<Conflict>,
+}
+
+impl MergeContext<'_> {
+    fn conflict(&mut self, field: &str, current: Value, incoming: Value, reason: ConflictReason) {
+        self.conflicts.push(Conflict {
+            entity_type: self.entity_type,
+            slug: self.slug.to_string(),
+            field: field.to_string(),
+            current,
+            incoming,
+            reason,
+        });
+    }
+
+    fn field<T: PartialEq + Serialize>(&mut self, field: &str, current: T, incoming: T) -> T {
+        if current == incoming || !self.locks.iter().any(|lock| lock == field) {
+            return incoming;
+        }
+        self.conflict(
+            field,
+            serde_json::json!(current),
+            serde_json::json!(incoming),
+            ConflictReason::Locked,
+        );
+        current
+    }
+
+    /// Incoming tags replace the imported ones, while tags added by humans are kept.
+    fn tags(
+        &mut self,
+        current: &[String],
+        human_tags: &[String],
+        incoming: &[String],
+    ) -> Vec<String> {
+        let mut merged: Vec<String> = Vec::with_capacity(incoming.len());
+        for tag in incoming {
+            if !merged.contains(tag) {
+                merged.push(tag.clone());
+            }
+        }
+        if same_tags(current, &merged) {
+            return current.to_vec();
+        }
+        if self.locks.iter().any(|lock| lock == "tags") {
+            self.conflict(
+                "tags",
+                serde_json::json!(current),
+                serde_json::json!(merged),
+                ConflictReason::Locked,
+            );
+            return current.to_vec();
+        }
+        for tag in human_tags {
+            if !merged.contains(tag) {
+                self.conflict(
+                    "tags",
+                    serde_json::json!(tag),
+                    Value::Null,
+                    ConflictReason::HumanTag,
+                );
+                merged.push(tag.clone());
+            }
+        }
+        merged
+    }
+}
+
+pub(super) fn same_tags(left: &[String], right: &[String]) -> bool {
+    left.iter().all(|tag| right.contains(tag)) && right.iter().all(|tag| left.contains(tag))
+}
+
+/// Merges the imported item into the current one.
+/// Returns the item to persist and the changes that could not be applied.
+pub(super) fn merge_item(
+    current: CurrentState<PersistedItem>,
+    incoming: Item,
+) -> (Item, Vec<Conflict>) {
+    let entity = current.entity;
+    let mut context = MergeContext {
+        entity_type: IndexedEntityType::Item,
+        slug: &entity.slug,
+        locks: current.locks,
+        conflicts: Vec::new(),
+    };
+    let name = context.field("name", entity.name.clone(), incoming.name);
+    let wiki_url = context.field("wiki_url", entity.wiki_url.clone(), incoming.wiki_url);
+    let effects_description = context.field(
+        "effects_description",
+        entity.effects_description.clone(),
+        incoming.effects_description,
+    );
+    let tags = context.tags(&entity.tags, current.human_tags, &incoming.tags);
+    let item = Item {
+        slug: entity.slug.clone(),
+        name,
+        wiki_url,
+        tags,
+        effects_description,
+    };
+    (item, context.conflicts)
+}
+
+/// Merges the imported ability into the current one.
+/// Returns the ability to persist and the changes that could not be applied.
+pub(super) fn merge_ability(
+    current: CurrentState<PersistedAbbreviatedAbility>,
+    incoming: AbbreviatedAbility,
+) -> (AbbreviatedAbility, Vec<Conflict>) {
+    let entity = current.entity;
+    let mut context = MergeContext {
+        entity_type: IndexedEntityType::Ability,
+        slug: &entity.slug,
+        locks: current.locks,
+        conflicts: Vec::new(),
+    };
+    let name = context.field("name", entity.name.clone(), incoming.name);
+    let wiki_url = context.field("wiki_url", entity.wiki_url.clone(), incoming.wiki_url);
+    let tags = context.tags(&entity.tags, current.human_tags, &incoming.tags);
+    let ability = AbbreviatedAbility {
+        slug: entity.slug.clone(),
+        name,
+        wiki_url,
+        tags,
+    };
+    (ability, context.conflicts)
+}
+
+pub(super) fn deleted_conflict(entity_type: IndexedEntityType, slug: &str) -> Conflict {
+    Conflict {
+        entity_type,
+        slug: slug.to_string(),
+        field: "deleted_at".to_string(),
+        current: Value::Null,
+        incoming: Value::Null,
+        reason: ConflictReason::Deleted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    fn current_item() -> PersistedItem {
+        PersistedItem {
+            id: 1,
+            name: "Drawn in Spring".to_string(),
+            slug: "drawn-in-spring".to_string(),
+            wiki_url: "https://example.com/old".to_string(),
+            tags: tags(&["freeze", "burn"]),
+            effects_description: "Edited by hand".to_string(),
+            verified: false,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn incoming_item() -> Item {
+        Item {
+            name: "Drawn in Spring".to_string(),
+            slug: "drawn-in-spring".to_string(),
+            wiki_url: "https://example.com/new".to_string(),
+            tags: tags(&["freeze", "shock"]),
+            effects_description: "From the wiki".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_item_keeps_locked_fields() {
+        let entity = current_item();
+        let locks = tags(&["effects_description"]);
+        let current = CurrentState {
+            entity: &entity,
+            locks: &locks,
+            human_tags: &[],
+        };
+        let (item, conflicts) = merge_item(current, incoming_item());
+        assert_eq!(item.wiki_url, "https://example.com/new");
+        assert_eq!(item.effects_description, "Edited by hand");
+        assert_eq!(item.tags, tags(&["freeze", "shock"]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].field, "effects_description");
+        assert_eq!(conflicts[0].reason, ConflictReason::Locked);
+    }
+
+    #[test]
+    fn test_merge_item_keeps_human_tags() {
+        let entity = current_item();
+        let human_tags = tags(&["burn"]);
+        let current = CurrentState {
+            entity: &entity,
+            locks: &[],
+            human_tags: &human_tags,
+        };
+        let (item, conflicts) = merge_item(current, incoming_item());
+        assert_eq!(item.tags, tags(&["freeze", "shock", "burn"]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].reason, ConflictReason::HumanTag);
+    }
+
+    #[test]
+    fn test_merge_item_keeps_the_slug_of_a_renamed_item() {
+        let entity = current_item();
+        let current = CurrentState {
+            entity: &entity,
+            locks: &[],
+            human_tags: &[],
+        };
+        let incoming = Item {
+            name: "Drawn in Spring (Renamed)".to_string(),
+            ..incoming_item()
+        };
+        let (item, _) = merge_item(current, incoming);
+        assert_eq!(item.name, "Drawn in Spring (Renamed)");
+        assert_eq!(item.slug, "drawn-in-spring");
+    }
+}
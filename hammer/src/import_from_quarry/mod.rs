@@ -1,5 +1,14 @@
+mod game_data;
 mod import_to_db;
+mod merge;
 mod read_abilities;
+mod read_csv;
 mod read_items;
+mod rollback;
+mod tags;
+mod validate;
 
-pub(crate) use import_to_db::import_to_db;
+pub(crate) use import_to_db::{InputFormat, SyncOptions, import_to_db};
+pub(crate) use rollback::rollback_import_run;
+pub(crate) use tags::{check_tag_vocabulary, sync_tags};
+pub(crate) use validate::validate_file;
@@ -1,20 +1,24 @@
+use super::validate::read_valid_records;
 use crate::error::Error;
-use crate::models::{AbbreviatedAbility, Ability, CONFIG};
-use std::path::Path;
+use crate::models::{AbbreviatedAbility, Ability, CONFIG, IndexedEntityType};
+use crate::resources::{self, Resource};
 
-pub(super) fn read_abilities() -> Result<Vec<AbbreviatedAbility>, Error> {
+/// Reads the abilities that match the JSON Schema, along with the number of skipped records.
+pub(super) fn read_abilities() -> Result<(Vec<AbbreviatedAbility>, usize), Error> {
     let path = &CONFIG.abilities_path;
-    let path = Path::new(path);
     tracing::trace!("abilities path: {path:?}");
 
-    let file_contents = std::fs::read_to_string(path)
+    let file_contents = resources::read_to_string(path, Resource::Abilities)
         .map_err(|err| format!("Failed to read the abilities files: {err:?}"))?;
-    serde_json::from_str::<Vec<Ability>>(&file_contents)
-        .map_err(|err| format!("Failed to parse abilities from string: {err:?}").into())
-        .map(|abilities| {
-            abilities
-                .into_iter()
+    let validated = read_valid_records(IndexedEntityType::Ability, &file_contents)?;
+    validated
+        .records
+        .into_iter()
+        .map(|record| {
+            serde_json::from_value::<Ability>(record)
                 .map(AbbreviatedAbility::from)
-                .collect()
+                .map_err(|err| format!("Failed to parse abilities from string: {err:?}").into())
         })
+        .collect::<Result<Vec<_>, Error>>()
+        .map(|abilities| (abilities, validated.skipped))
 }
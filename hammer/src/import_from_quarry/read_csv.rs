@@ -0,0 +1,174 @@
+use crate::error::Error;
+use crate::models::{AbbreviatedAbility, CONFIG, Item};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub(super) const ITEMS_FILE: &str = "deadfire_items.csv";
+pub(super) const ABILITY_FILES: [&str; 3] = [
+    "deadfire_active_abilities.csv",
+    "deadfire_modal_abilities.csv",
+    "deadfire_passive_abilities.csv",
+];
+const URL_COLUMN: &str = "URL";
+
+/// A row of the quarry CSV exports. They only list the names and wiki URLs.
+#[derive(Debug, PartialEq, Eq)]
+struct CsvRecord {
+    name: String,
+    url: String,
+}
+
+pub(super) fn csv_path(file: &str) -> PathBuf {
+    Path::new(&CONFIG.quarry_csv_dir).join(file)
+}
+
+/// Checks that the header has exactly the expected columns.
+fn check_columns(path: &Path, headers: &csv::StringRecord, expected: &[&str]) -> Result<(), Error> {
+    let unknown: Vec<_> = headers
+        .iter()
+        .filter(|column| !expected.contains(column))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Unknown columns in {}: {}. Expected the columns: {}",
+            path.display(),
+            unknown.join(", "),
+            expected.join(", ")
+        )
+        .into());
+    }
+    let missing: Vec<_> = expected
+        .iter()
+        .filter(|column| !headers.iter().any(|header| &header == *column))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Missing columns in {}: {}",
+            path.display(),
+            missing.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn read_records<R: std::io::Read>(
+    path: &Path,
+    reader: R,
+    name_column: &str,
+) -> Result<Vec<CsvRecord>, Error> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|err| format!("Failed to read the header of {}: {err}", path.display()))?
+        .clone();
+    check_columns(path, &headers, &[name_column, URL_COLUMN])?;
+    let name_index = headers.iter().position(|h| h == name_column).unwrap();
+    let url_index = headers.iter().position(|h| h == URL_COLUMN).unwrap();
+
+    let mut records = Vec::new();
+    for (line, row) in reader.records().enumerate() {
+        let row = row.map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let name = row.get(name_index).unwrap_or_default().trim();
+        let url = row.get(url_index).unwrap_or_default().trim();
+        // The scrapers also list the wiki category pages, which are not entities
+        if name.is_empty() || url.contains("/wiki/Category:") {
+            tracing::trace!("Skipping line {} of {}", line + 2, path.display());
+            continue;
+        }
+        records.push(CsvRecord {
+            name: name.to_string(),
+            url: url.to_string(),
+        });
+    }
+    Ok(records)
+}
+
+fn read_file(file: &str, name_column: &str) -> Result<Vec<CsvRecord>, Error> {
+    let path = csv_path(file);
+    tracing::trace!("csv path: {path:?}");
+    let contents = std::fs::File::open(&path)
+        .map_err(|err| format!("Failed to open {}: {err:?}", path.display()))?;
+    read_records(&path, contents, name_column)
+}
+
+/// Keeps the first record of every slug. The exports list some entities more than once.
+fn unique_by_slug(records: Vec<CsvRecord>) -> Vec<(String, CsvRecord)> {
+    let mut seen = HashSet::new();
+    records
+        .into_iter()
+        .map(|record| (slug::slugify(&record.name), record))
+        .filter(|(slug, _)| seen.insert(slug.clone()))
+        .collect()
+}
+
+/// Reads the items CSV. The items have no tags or effects, the import keeps the current ones.
+pub(super) fn read_items_csv() -> Result<Vec<Item>, Error> {
+    let records = read_file(ITEMS_FILE, "Item Name")?;
+    Ok(unique_by_slug(records)
+        .into_iter()
+        .map(|(slug, record)| Item {
+            name: record.name,
+            slug,
+            wiki_url: record.url,
+            tags: Vec::new(),
+            effects_description: String::new(),
+        })
+        .collect())
+}
+
+/// Reads the active, modal and passive abilities CSVs. The abilities have no tags,
+/// the import keeps the current ones.
+pub(super) fn read_abilities_csv() -> Result<Vec<AbbreviatedAbility>, Error> {
+    let mut records = Vec::new();
+    for file in ABILITY_FILES {
+        records.extend(read_file(file, "Ability Name")?);
+    }
+    Ok(unique_by_slug(records)
+        .into_iter()
+        .map(|(slug, record)| AbbreviatedAbility {
+            name: record.name,
+            slug,
+            tags: Vec::new(),
+            wiki_url: record.url,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_records_skips_categories() {
+        let contents = "Item Name,URL\n\
+            ,https://example.com/wiki/Category:Items\n\
+            Beast of Winter items,https://example.com/wiki/Category:Beast_of_Winter_items\n\
+            A Whale of a Wand,https://example.com/wiki/A_Whale_of_a_Wand\n";
+
+        let records = read_records(Path::new("items.csv"), contents.as_bytes(), "Item Name");
+
+        assert_eq!(
+            records.unwrap(),
+            vec![CsvRecord {
+                name: "A Whale of a Wand".to_string(),
+                url: "https://example.com/wiki/A_Whale_of_a_Wand".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_columns_are_reported() {
+        let contents =
+            "Item Name,URL,Rarity,Price\nA Whale of a Wand,https://example.com,Rare,10\n";
+
+        let err =
+            read_records(Path::new("items.csv"), contents.as_bytes(), "Item Name").unwrap_err();
+
+        assert_eq!(
+            err.0,
+            "Unknown columns in items.csv: Rarity, Price. Expected the columns: Item Name, URL"
+        );
+    }
+}
@@ -1,16 +1,25 @@
+use super::validate::read_valid_records;
 use crate::error::Error;
 use crate::models::CONFIG;
-use crate::models::{Item, JsonItem};
-use std::path::Path;
+use crate::models::{IndexedEntityType, Item, JsonItem};
+use crate::resources::{self, Resource};
 
-pub(super) fn read_items() -> Result<Vec<Item>, Error> {
+/// Reads the items that match the JSON Schema, along with the number of skipped records.
+pub(super) fn read_items() -> Result<(Vec<Item>, usize), Error> {
     let path = &CONFIG.items_path;
-    let path = Path::new(path);
     tracing::trace!("items path: {path:?}");
 
-    let file_contents = std::fs::read_to_string(path)
+    let file_contents = resources::read_to_string(path, Resource::Items)
         .map_err(|err| format!("Failed to read the items files: {err:?}"))?;
-    serde_json::from_str::<Vec<JsonItem>>(&file_contents)
-        .map_err(|err| format!("Failed to parse items from string: {err:?}").into())
-        .map(|items| items.into_iter().map(Item::from).collect())
+    let validated = read_valid_records(IndexedEntityType::Item, &file_contents)?;
+    validated
+        .records
+        .into_iter()
+        .map(|record| {
+            serde_json::from_value::<JsonItem>(record)
+                .map(Item::from)
+                .map_err(|err| format!("Failed to parse items from string: {err:?}").into())
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .map(|items| (items, validated.skipped))
 }
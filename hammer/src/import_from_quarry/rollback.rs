@@ -0,0 +1,264 @@
+use super::merge;
+use crate::db;
+use crate::error::{Error, ErrorType};
+use crate::models::{
+    AbbreviatedAbility, ChangeAction, ChangeEvent, ImportRunChange, IndexedEntityType, Item,
+    RollbackSummary, TagSource, changed_fields,
+};
+use crate::webhooks;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use serde::de::DeserializeOwned;
+
+/// Reverts the changes of an import run, latest change first.
+///
+/// Entities that were changed after the run, by an editor or a later import, are left alone
+/// and reported as skipped. The whole rollback happens in a single transaction, which also
+/// queues the webhook deliveries of the reverted changes. Returns the summary and the reverted
+/// changes, made by `author`.
+pub(crate) fn rollback_import_run(
+    id: i64,
+    author: &str,
+    conn: &mut Connection,
+) -> Result<(RollbackSummary, Vec<ChangeEvent>), Error> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let run = db::import_run::find_by_id(id, &tx)?.ok_or(Error(
+        format!("Import run {id} does not exist"),
+        ErrorType::NotFound,
+    ))?;
+    if run.rolled_back_at.is_some() {
+        return Err(Error(
+            format!("Import run {id} has already been rolled back"),
+            ErrorType::Conflict,
+        ));
+    }
+
+    let source = TagSource::Import(format!("rollback-{id}"));
+    let mut summary = RollbackSummary::default();
+    let mut events = Vec::new();
+    for change in db::import_run::find_changes(id, &tx)? {
+        let reverted = match change.entity_type {
+            IndexedEntityType::Item => revert_item(&change, &source, &tx)?,
+            IndexedEntityType::Ability => revert_ability(&change, &source, &tx)?,
+        };
+        if !reverted {
+            summary.skipped.push(change.slug);
+            continue;
+        }
+        summary.reverted += 1;
+        let event = reverting_event(change, author)?;
+        webhooks::enqueue(&event, &tx)?;
+        events.push(event);
+    }
+    db::import_run::mark_rolled_back(id, &tx)?;
+    tx.commit()?;
+    Ok((summary, events))
+}
+
+/// The change that reverting the change of the import run makes.
+fn reverting_event(change: ImportRunChange, author: &str) -> Result<ChangeEvent, Error> {
+    let (action, fields) = match change.action {
+        ChangeAction::Created => (ChangeAction::Removed, vec![]),
+        ChangeAction::Removed => (ChangeAction::Created, vec![]),
+        ChangeAction::Updated => {
+            let after: serde_json::Value = parse_snapshot(&change.after)?;
+            let before: serde_json::Value = parse_snapshot(&change.before)?;
+            (ChangeAction::Updated, changed_fields(&after, &before)?)
+        }
+    };
+    Ok(ChangeEvent {
+        entity_type: change.entity_type,
+        slug: change.slug,
+        action,
+        fields,
+        author: author.to_string(),
+    })
+}
+
+fn revert_item(
+    change: &ImportRunChange,
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<bool, Error> {
+    if change.action == ChangeAction::Removed {
+        return db::item::restore(&change.slug, tx);
+    }
+    let after: Item = parse_snapshot(&change.after)?;
+    let Some(current) = db::item::find_by_slug(&change.slug, tx)? else {
+        return Ok(false);
+    };
+    if current.name != after.name
+        || current.wiki_url != after.wiki_url
+        || current.effects_description != after.effects_description
+        || !merge::same_tags(&current.tags, &after.tags)
+    {
+        return Ok(false);
+    }
+    match change.action {
+        ChangeAction::Created => {
+            db::item::delete(&change.slug, tx)?;
+            db::item::purge(&change.slug, tx)
+        }
+        _ => {
+            let before: Item = parse_snapshot(&change.before)?;
+            db::item::update_in_transaction(&change.slug, &before, source, tx)?;
+            Ok(true)
+        }
+    }
+}
+
+fn revert_ability(
+    change: &ImportRunChange,
+    source: &TagSource,
+    tx: &Transaction,
+) -> Result<bool, Error> {
+    if change.action == ChangeAction::Removed {
+        return db::ability::restore(&change.slug, tx);
+    }
+    let after: AbbreviatedAbility = parse_snapshot(&change.after)?;
+    let Some(current) = db::ability::find_by_slug(&change.slug, tx)? else {
+        return Ok(false);
+    };
+    if current.name != after.name
+        || current.wiki_url != after.wiki_url
+        || !merge::same_tags(&current.tags, &after.tags)
+    {
+        return Ok(false);
+    }
+    match change.action {
+        ChangeAction::Created => {
+            db::ability::delete(&change.slug, tx)?;
+            db::ability::purge(&change.slug, tx)
+        }
+        _ => {
+            let before: AbbreviatedAbility = parse_snapshot(&change.before)?;
+            db::ability::update_abbreviated_ability_in_transaction(
+                &change.slug,
+                before,
+                source,
+                tx,
+            )?;
+            Ok(true)
+        }
+    }
+}
+
+fn parse_snapshot<T: DeserializeOwned>(snapshot: &Option<String>) -> Result<T, Error> {
+    let snapshot = snapshot
+        .as_deref()
+        .ok_or("The import run change is missing its snapshot")?;
+    serde_json::from_str(snapshot)
+        .map_err(|err| format!("Failed to parse the entity snapshot: {err:?}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import_from_quarry::SyncOptions;
+    use crate::import_from_quarry::import_to_db::sync_records;
+
+    fn item(name: &str, wiki_url: &str) -> Item {
+        Item {
+            name: name.to_string(),
+            slug: slug::slugify(name),
+            wiki_url: wiki_url.to_string(),
+            tags: vec!["freeze".to_string()],
+            effects_description: String::new(),
+        }
+    }
+
+    fn ability(name: &str) -> AbbreviatedAbility {
+        AbbreviatedAbility {
+            name: name.to_string(),
+            slug: slug::slugify(name),
+            wiki_url: String::new(),
+            tags: vec!["freeze".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_rollback_reverts_the_created_updated_and_removed_entities() {
+        let mut conn = db::get_test_connection();
+        sync_records(
+            vec![item("Torch", "old"), item("Drawn in Spring", "")],
+            vec![ability("Frostfire")],
+            SyncOptions::default(),
+            &mut conn,
+        )
+        .unwrap();
+        let options = SyncOptions {
+            remove_missing: true,
+            ..Default::default()
+        };
+        let run = sync_records(
+            vec![item("Torch", "new"), item("Lantern", "")],
+            vec![ability("Frostfire"), ability("Fan of Flames")],
+            options,
+            &mut conn,
+        )
+        .unwrap();
+
+        let (summary, events) = rollback_import_run(run.run_id, "cli", &mut conn).unwrap();
+        assert_eq!(summary.reverted, 4);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(events.len(), 4);
+        let torch = db::item::find_by_slug("torch", &conn).unwrap().unwrap();
+        assert_eq!(torch.wiki_url, "old");
+        assert!(
+            db::item::find_by_slug("drawn-in-spring", &conn)
+                .unwrap()
+                .is_some()
+        );
+        assert!(db::item::find_by_slug("lantern", &conn).unwrap().is_none());
+        assert!(db::item::find_deleted(&conn).unwrap().is_empty());
+        assert!(
+            db::ability::find_by_slug("fan-of-flames", &conn)
+                .unwrap()
+                .is_none()
+        );
+        assert!(db::ability::find_deleted(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rollback_skips_the_entities_edited_after_the_run() {
+        let mut conn = db::get_test_connection();
+        let run = sync_records(
+            vec![item("Torch", ""), item("Lantern", "")],
+            vec![],
+            SyncOptions::default(),
+            &mut conn,
+        )
+        .unwrap();
+        let tx = conn.transaction().unwrap();
+        db::item::update_in_transaction(
+            "torch",
+            &item("Torch", "edited"),
+            &TagSource::Editor("editor@example.com".to_string()),
+            &tx,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let (summary, _) = rollback_import_run(run.run_id, "cli", &mut conn).unwrap();
+        assert_eq!(summary.reverted, 1);
+        assert_eq!(summary.skipped, ["torch"]);
+        let torch = db::item::find_by_slug("torch", &conn).unwrap().unwrap();
+        assert_eq!(torch.wiki_url, "edited");
+        assert!(db::item::find_by_slug("lantern", &conn).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_a_run_can_only_be_rolled_back_once() {
+        let mut conn = db::get_test_connection();
+        let run = sync_records(
+            vec![item("Torch", "")],
+            vec![],
+            SyncOptions::default(),
+            &mut conn,
+        )
+        .unwrap();
+
+        rollback_import_run(run.run_id, "cli", &mut conn).unwrap();
+        let err = rollback_import_run(run.run_id, "cli", &mut conn).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict));
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use super::validate::load_schema;
+use crate::db;
+use crate::error::Error;
+use crate::models::{CONFIG, IndexedEntityType};
+use crate::resources::{self, Resource};
+use rusqlite::{Connection, TransactionBehavior};
+
+/// Where the tag enum lives in the JSON Schema of each entity type.
+const ITEM_SCHEMA_TAGS: &str = "/properties/tags/items/enum";
+const ABILITY_SCHEMA_TAGS: &str = "/properties/effects/items/properties/tags/items/enum";
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct VocabularyTag {
+    pub(super) name: String,
+    pub(super) description: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TagSyncSummary {
+    pub(crate) added: Vec<String>,
+    pub(crate) updated: usize,
+    /// Tags in the database that the vocabulary no longer lists. They are kept because
+    /// removing them would strip them from every item and ability.
+    pub(crate) unlisted: Vec<String>,
+}
+
+impl Display for TagSyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} descriptions updated",
+            self.added.len(),
+            self.updated
+        )?;
+        if !self.unlisted.is_empty() {
+            write!(f, ", not in the vocabulary: {}", self.unlisted.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `tags.md`. Every line is `<number>: <tag>`, optionally followed by `# <description>`.
+pub(super) fn parse_vocabulary(contents: &str) -> Result<Vec<VocabularyTag>, Error> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let (_, entry) = line.split_once(':').ok_or(format!(
+                "Line {} of the tag list is not `<number>: <tag>`",
+                index + 1
+            ))?;
+            let (name, description) = match entry.split_once('#') {
+                Some((name, description)) => (name, Some(description.trim().to_string())),
+                None => (entry, None),
+            };
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!("Line {} of the tag list has no valid tag", index + 1).into());
+            }
+            Ok(VocabularyTag {
+                name: name.to_string(),
+                description,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn read_vocabulary() -> Result<Vec<VocabularyTag>, Error> {
+    let path = &CONFIG.tags_path;
+    let contents = resources::read_to_string(path, Resource::Tags)
+        .map_err(|err| format!("Failed to read the tag list {path}: {err:?}"))?;
+    parse_vocabulary(&contents)
+}
+
+/// Adds the tags of the vocabulary that are missing from the database and updates the
+/// descriptions the vocabulary provides.
+pub(super) fn sync_tags_in_transaction(
+    vocabulary: &[VocabularyTag],
+    conn: &Connection,
+) -> Result<TagSyncSummary, Error> {
+    let existing: BTreeSet<String> = db::tag::find_all(conn)?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+
+    let mut summary = TagSyncSummary::default();
+    for tag in vocabulary {
+        if !existing.contains(&tag.name) {
+            let description = tag.description.as_deref().unwrap_or_default();
+            db::tag::insert(&tag.name, description, conn)?;
+            summary.added.push(tag.name.clone());
+        } else if let Some(description) = &tag.description
+            && db::tag::update_description(&tag.name, description, conn)?
+        {
+            summary.updated += 1;
+        }
+    }
+    summary.unlisted = existing
+        .into_iter()
+        .filter(|name| !vocabulary.iter().any(|tag| &tag.name == name))
+        .collect();
+    Ok(summary)
+}
+
+/// Synchronizes the `tags` table with the vocabulary in a single transaction.
+pub(crate) fn sync_tags() -> Result<TagSyncSummary, Error> {
+    let vocabulary = read_vocabulary()?;
+    let mut conn = db::get_connection()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let summary = sync_tags_in_transaction(&vocabulary, &tx)?;
+    tx.commit()?;
+    Ok(summary)
+}
+
+fn schema_tags(entity_type: IndexedEntityType) -> Result<Vec<String>, Error> {
+    let pointer = match entity_type {
+        IndexedEntityType::Item => ITEM_SCHEMA_TAGS,
+        IndexedEntityType::Ability => ABILITY_SCHEMA_TAGS,
+    };
+    let schema = load_schema(entity_type)?;
+    let tags = schema
+        .pointer(pointer)
+        .and_then(|tags| tags.as_array())
+        .ok_or(format!(
+            "The {} schema has no tag enum at {pointer}",
+            entity_type.as_str()
+        ))?;
+    Ok(tags
+        .iter()
+        .filter_map(|tag| tag.as_str().map(str::to_string))
+        .collect())
+}
+
+/// Describes how a copy of the tag list differs from the vocabulary.
+fn differences(copy: &str, vocabulary: &[String], tags: &[String]) -> Vec<String> {
+    let missing: Vec<_> = vocabulary
+        .iter()
+        .filter(|tag| !tags.contains(tag))
+        .map(String::as_str)
+        .collect();
+    let extra: Vec<_> = tags
+        .iter()
+        .filter(|tag| !vocabulary.contains(tag))
+        .map(String::as_str)
+        .collect();
+
+    let mut differences = Vec::new();
+    if !missing.is_empty() {
+        differences.push(format!(
+            "The {copy} is missing tags from the tag list: {}",
+            missing.join(", ")
+        ));
+    }
+    if !extra.is_empty() {
+        differences.push(format!(
+            "The {copy} has tags that are not in the tag list: {}",
+            extra.join(", ")
+        ));
+    }
+    differences
+}
+
+/// Compares the tags in the database and the schema enums with the tag list.
+/// Returns a description of every disagreement.
+pub(crate) fn check_tag_vocabulary(conn: &Connection) -> Result<Vec<String>, Error> {
+    let vocabulary: Vec<String> = read_vocabulary()?.into_iter().map(|tag| tag.name).collect();
+    let db_tags: Vec<String> = db::tag::find_all(conn)?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+
+    let mut disagreements = differences("database", &vocabulary, &db_tags);
+    disagreements.extend(differences(
+        "item schema enum",
+        &vocabulary,
+        &schema_tags(IndexedEntityType::Item)?,
+    ));
+    disagreements.extend(differences(
+        "ability schema enum",
+        &vocabulary,
+        &schema_tags(IndexedEntityType::Ability)?,
+    ));
+    Ok(disagreements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vocabulary() {
+        let contents = "0: barbarian\n\n126: special # otherwise undefined\n";
+
+        let vocabulary = parse_vocabulary(contents).unwrap();
+
+        assert_eq!(
+            vocabulary,
+            vec![
+                VocabularyTag {
+                    name: "barbarian".to_string(),
+                    description: None,
+                },
+                VocabularyTag {
+                    name: "special".to_string(),
+                    description: Some("otherwise undefined".to_string()),
+                },
+            ]
+        );
+        assert!(parse_vocabulary("barbarian").is_err());
+    }
+
+    #[test]
+    fn test_differences() {
+        let vocabulary = vec!["burn".to_string(), "freeze".to_string()];
+        let tags = vec!["burn".to_string(), "fire".to_string()];
+
+        let differences = differences("database", &vocabulary, &tags);
+
+        assert_eq!(
+            differences,
+            vec![
+                "The database is missing tags from the tag list: freeze",
+                "The database has tags that are not in the tag list: fire",
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt::Display;
+
+use crate::error::Error;
+use crate::models::{CONFIG, IndexedEntityType, Strictness};
+use crate::resources::{self, Resource};
+use serde_json::Value;
+
+/// A violation of the JSON Schema. The path is a JSON pointer into the validated file.
+#[derive(Debug, Clone)]
+pub(crate) struct InvalidRecord {
+    pub(crate) path: String,
+    pub(crate) name: Option<String>,
+    pub(crate) message: String,
+}
+
+impl Display for InvalidRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({name}): {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Validated {
+    pub(super) records: Vec<Value>,
+    pub(super) invalid: Vec<InvalidRecord>,
+    /// The number of records left out because they did not match the schema.
+    pub(super) skipped: usize,
+}
+
+fn schema_path(entity_type: IndexedEntityType) -> (&'static str, Resource) {
+    match entity_type {
+        IndexedEntityType::Item => (&CONFIG.items_schema_path, Resource::ItemsSchema),
+        IndexedEntityType::Ability => (&CONFIG.abilities_schema_path, Resource::AbilitiesSchema),
+    }
+}
+
+pub(super) fn load_schema(entity_type: IndexedEntityType) -> Result<Value, Error> {
+    let (path, resource) = schema_path(entity_type);
+    let contents = resources::read_to_string(path, resource)
+        .map_err(|err| format!("Failed to read the JSON Schema {path}: {err:?}"))?;
+    serde_json::from_str(&contents)
+        .map_err(|err| format!("Failed to parse the JSON Schema {path}: {err:?}").into())
+}
+
+/// Validates every record of a quarry JSON file against the schema.
+pub(super) fn validate_records(schema: &Value, contents: &str) -> Result<Validated, Error> {
+    let validator =
+        jsonschema::validator_for(schema).map_err(|err| format!("Invalid JSON Schema: {err}"))?;
+    let records = match serde_json::from_str(contents)
+        .map_err(|err| format!("Failed to parse the JSON file: {err:?}"))?
+    {
+        Value::Array(records) => records,
+        _ => return Err("Expected a JSON array of records".into()),
+    };
+
+    let mut validated = Validated::default();
+    for (index, record) in records.into_iter().enumerate() {
+        let name = record
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let errors: Vec<_> = validator
+            .iter_errors(&record)
+            .map(|err| InvalidRecord {
+                path: format!("/{index}{}", err.instance_path),
+                name: name.clone(),
+                message: message(&err),
+            })
+            .collect();
+        if errors.is_empty() {
+            validated.records.push(record);
+        } else {
+            validated.skipped += 1;
+            validated.invalid.extend(errors);
+        }
+    }
+    Ok(validated)
+}
+
+/// Describes the violation. Enum violations leave out the list of allowed values, which for
+/// tags is the whole vocabulary.
+fn message(err: &jsonschema::ValidationError) -> String {
+    match err.kind {
+        jsonschema::error::ValidationErrorKind::Enum { .. } => {
+            format!("{} is not an allowed value", err.instance)
+        }
+        _ => err.to_string(),
+    }
+}
+
+/// Validates a quarry JSON file and applies the configured strictness to the invalid records.
+pub(super) fn read_valid_records(
+    entity_type: IndexedEntityType,
+    contents: &str,
+) -> Result<Validated, Error> {
+    let validated = validate_records(&load_schema(entity_type)?, contents)?;
+    if validated.invalid.is_empty() {
+        return Ok(validated);
+    }
+    match CONFIG.import_strictness {
+        Strictness::Reject => Err(format!(
+            "{} invalid {} records:\n{}",
+            validated.skipped,
+            entity_type.as_str(),
+            validated
+                .invalid
+                .iter()
+                .map(InvalidRecord::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+        .into()),
+        Strictness::Skip => {
+            for invalid in &validated.invalid {
+                tracing::warn!("Skipping invalid {} record {invalid}", entity_type.as_str());
+            }
+            Ok(validated)
+        }
+    }
+}
+
+/// Validates a quarry JSON file without importing it. Files whose records have `effects`
+/// are validated as abilities, everything else as items.
+pub(crate) fn validate_file(path: &str) -> Result<Vec<InvalidRecord>, Error> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| format!("Failed to read {path}: {err:?}"))?;
+    let entity_type = if contents_look_like_abilities(&contents) {
+        IndexedEntityType::Ability
+    } else {
+        IndexedEntityType::Item
+    };
+    tracing::debug!("Validating {path} as {}", entity_type.as_str());
+    Ok(validate_records(&load_schema(entity_type)?, &contents)?.invalid)
+}
+
+fn contents_look_like_abilities(contents: &str) -> bool {
+    serde_json::from_str::<Value>(contents)
+        .ok()
+        .and_then(|value| value.get(0).map(|record| record.get("effects").is_some()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_every_invalid_record_is_reported_with_its_path() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "properties": {
+                "name": { "type": "string" },
+                "tags": { "type": "array", "items": { "enum": ["burn", "freeze"] } }
+            }
+        });
+        let contents = r#"[
+            { "name": "Valid", "tags": ["burn"] },
+            { "name": "Bad tag", "tags": ["freeze", "fire"] },
+            { "tags": [] }
+        ]"#;
+
+        let validated = validate_records(&schema, contents).unwrap();
+
+        assert_eq!(validated.records.len(), 1);
+        assert_eq!(validated.skipped, 2);
+        let paths: Vec<_> = validated.invalid.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, vec!["/1/tags/1", "/2"]);
+        assert_eq!(validated.invalid[0].name.as_deref(), Some("Bad tag"));
+        assert_eq!(
+            validated.invalid[0].message,
+            "\"fire\" is not an allowed value"
+        );
+    }
+}
@@ -1,19 +1,31 @@
 pub(crate) mod auth;
+pub(crate) mod cli;
 pub(crate) mod db;
 pub(crate) mod error;
 pub(crate) mod import_from_quarry;
 pub(crate) mod models;
+pub(crate) mod resources;
 pub(crate) mod routes;
+pub(crate) mod server;
+pub(crate) mod tag_index;
+pub(crate) mod webhooks;
 
-use crate::{import_from_quarry::import_to_db, models::CONFIG, routes::get_backend_routes};
+use crate::{
+    cli::{Cli, Command},
+    models::{CONFIG, override_config},
+    routes::get_backend_routes,
+};
 use axum::{Router, http::StatusCode, routing::get};
+use clap::Parser;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<_> = std::env::args().collect();
+    let cli = Cli::parse();
+    // The overrides must be set before anything reads the configuration
+    override_config(cli.config.into());
 
     // Initialize tracing
     tracing_subscriber::registry()
@@ -23,50 +35,36 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Remove the db if reset-db parameter is present
-    if args.iter().any(|arg| arg.as_str() == "--reset-db") {
-        reset_db();
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        command => {
+            if let Err(e) = cli::run(command) {
+                eprintln!("{}", e.0);
+                std::process::exit(1);
+            }
+        }
     }
+}
 
-    // Synchronize database
-    let conn = db::get_connection().expect("Failed to get DB connection");
-    db::synchronize_db(&conn).expect("Failed to synchronize DB");
-    drop(conn);
+async fn serve() {
+    cli::prepare_db().expect("Failed to synchronize DB");
+    cli::seed_empty_db().expect("Failed to seed the database");
 
-    // Import files to database if needed
-    if args
-        .iter()
-        .any(|arg| arg.as_str() == "--import-from-quarry")
-    {
-        import_from_quarry();
-        return;
+    let pool = db::create_pool().expect("Failed to create the database connection pool");
+    if CONFIG.tag_index {
+        let conn = pool.get().expect("Failed to get a database connection");
+        tag_index::build(&conn).expect("Failed to build the tag index");
     }
-
-    let app = Router::<()>::new()
+    webhooks::start(pool.clone()).expect("Failed to start the webhook worker");
+    let app = Router::new()
         .route("/health", get(|| async { StatusCode::OK }))
-        .nest("/api", get_backend_routes().merge(auth::auth_routes()))
-        .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
+        .nest("/api", get_backend_routes(&pool).merge(auth::auth_routes()))
+        .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
+        .with_state(pool);
 
     // Run it
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
-    tracing::debug!("TRACE: Listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
-}
-
-fn import_from_quarry() {
-    match import_to_db() {
-        Ok(_) => {
-            tracing::info!("Successfully imported abilities and items from JSON files to database")
-        }
-        Err(e) => panic!("Failed to import abilities and items to database: {e:?}"),
-    }
-}
-
-fn reset_db() {
-    match std::fs::remove_file(&CONFIG.db_path) {
-        Ok(_) => tracing::debug!("Removed the database"),
-        Err(err) => tracing::warn!(
-            "Failed to remove the database. Remove the db file manually. Error: {err}"
-        ),
+    if let Err(e) = server::serve(app).await {
+        eprintln!("{}", e.0);
+        std::process::exit(1);
     }
 }
@@ -1,6 +1,9 @@
 use super::Ability;
 use serde::{Deserialize, Serialize};
 
+/// Fields of an ability that editors can lock against changes from imports.
+pub(crate) const ABILITY_LOCKABLE_FIELDS: [&str; 3] = ["name", "wiki_url", "tags"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct AbbreviatedAbility {
     pub(crate) name: String,
@@ -9,13 +12,16 @@ pub(crate) struct AbbreviatedAbility {
     pub(crate) wiki_url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct PersistedAbbreviatedAbility {
     pub(crate) id: i64,
     pub(crate) slug: String,
     pub(crate) name: String,
     pub(crate) tags: Vec<String>,
     pub(crate) wiki_url: String,
+    pub(crate) verified: bool,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
 }
 
 impl From<PersistedAbbreviatedAbility> for AbbreviatedAbility {
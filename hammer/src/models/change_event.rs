@@ -0,0 +1,58 @@
+use crate::error::Error;
+use crate::models::{ChangeAction, IndexedEntityType};
+use serde::Serialize;
+
+/// A committed change to an entity, as `/api/events` broadcasts it to the open pages.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub(crate) struct ChangeEvent {
+    #[serde(rename = "type")]
+    pub(crate) entity_type: IndexedEntityType,
+    /// The slug before the change, which is the one the pages know.
+    pub(crate) slug: String,
+    pub(crate) action: ChangeAction,
+    /// The fields of the entity that changed. Empty when it was created or removed.
+    pub(crate) fields: Vec<String>,
+    /// The email of the editor who made the change, or `cli` for a change made on the command
+    /// line.
+    pub(crate) author: String,
+}
+
+/// The top-level fields whose values differ between the two representations.
+pub(crate) fn changed_fields<T: Serialize>(before: &T, after: &T) -> Result<Vec<String>, Error> {
+    let to_json = |value: &T| serde_json::to_value(value).map_err(|err| err.to_string());
+    let (before, after) = (to_json(before)?, to_json(after)?);
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Err("Only objects have fields".into());
+    };
+    Ok(after
+        .iter()
+        .filter(|(field, value)| before.get(*field) != Some(*value))
+        .map(|(field, _)| field.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Item;
+
+    #[test]
+    fn test_changed_fields() {
+        let before = Item {
+            name: "Torch".to_string(),
+            slug: "torch".to_string(),
+            wiki_url: String::new(),
+            tags: vec!["burn".to_string()],
+            effects_description: String::new(),
+        };
+        let after = Item {
+            tags: vec!["burn".to_string(), "heal".to_string()],
+            effects_description: "Lights the way".to_string(),
+            ..before.clone()
+        };
+        let mut fields = changed_fields(&before, &after).unwrap();
+        fields.sort();
+        assert_eq!(fields, ["effects_description", "tags"]);
+        assert!(changed_fields(&before, &before).unwrap().is_empty());
+    }
+}
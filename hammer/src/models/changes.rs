@@ -0,0 +1,26 @@
+use crate::models::{AbbreviatedAbility, IndexedEntityType, Item, Timestamped};
+use serde::Serialize;
+
+/// What changed since a point in time. A client applies `deleted` before the entities, since
+/// a slug can be deleted and then taken by a new entity.
+#[derive(Debug, Serialize)]
+pub(crate) struct Changes {
+    /// The `since` of the next request, to get the changes after these. An opaque token, not a
+    /// time.
+    pub(crate) next: String,
+    /// The items created or changed since then.
+    pub(crate) items: Vec<Timestamped<Item>>,
+    /// The abilities created or changed since then.
+    pub(crate) abilities: Vec<Timestamped<AbbreviatedAbility>>,
+    pub(crate) deleted: Vec<DeletedEntity>,
+}
+
+/// A slug that no longer has a live entity, because the entity was moved to the trash,
+/// purged, or renamed.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub(crate) struct DeletedEntity {
+    pub(crate) slug: String,
+    pub(crate) deleted_at: String,
+    #[serde(rename = "type")]
+    pub(crate) entity_type: IndexedEntityType,
+}
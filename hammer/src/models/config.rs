@@ -1,14 +1,41 @@
 use serde::{Deserialize, Serialize};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
+
+/// Overrides of the configuration file and its fields, e.g. from the command line.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigOverrides {
+    pub(crate) config_file: Option<String>,
+    /// Pairs of a `Config` field and its value.
+    pub(crate) fields: Vec<(&'static str, String)>,
+}
+
+static CONFIG_OVERRIDES: OnceLock<ConfigOverrides> = OnceLock::new();
+
+/// Sets the overrides. They only take effect if `CONFIG` has not been used yet.
+pub(crate) fn override_config(overrides: ConfigOverrides) {
+    if CONFIG_OVERRIDES.set(overrides).is_err() {
+        tracing::warn!("The configuration overrides were already set");
+    }
+}
 
 pub(crate) static CONFIG: LazyLock<Config> = LazyLock::new(|| {
-    let config_file =
-        std::env::var("HAMMER_CONFIG_FILE").unwrap_or_else(|_| "./hammer.toml".into());
-    let settings = match config::Config::builder()
-        .add_source(config::File::with_name(&config_file))
-        .add_source(config::Environment::with_prefix("HAMMER"))
-        .build()
-    {
+    let overrides = CONFIG_OVERRIDES.get_or_init(ConfigOverrides::default);
+    let config_file = overrides
+        .config_file
+        .clone()
+        .or_else(|| std::env::var("HAMMER_CONFIG_FILE").ok());
+    // Only a configuration file that was asked for explicitly has to exist
+    let file_source = match &config_file {
+        Some(config_file) => config::File::with_name(config_file),
+        None => config::File::with_name("./hammer.toml").required(false),
+    };
+    let builder = overrides.fields.iter().try_fold(
+        config::Config::builder()
+            .add_source(file_source)
+            .add_source(config::Environment::with_prefix("HAMMER")),
+        |builder, (field, value)| builder.set_override(*field, value.as_str()),
+    );
+    let settings = match builder.and_then(|builder| builder.build()) {
         Ok(settings) => settings,
         Err(err) => {
             tracing::error!("Failed to load configuration file or environment variables. {err:?}");
@@ -25,11 +52,180 @@ pub(crate) static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     }
 });
 
+fn default_abilities_path() -> String {
+    String::from("./abilities.json")
+}
+
+fn default_items_path() -> String {
+    String::from("./items.json")
+}
+
+fn default_db_path() -> String {
+    String::from("./hammer.db3")
+}
+
+fn default_db_migrations() -> String {
+    String::from("./resources/db")
+}
+
+fn default_db_busy_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_db_pool_size() -> u32 {
+    8
+}
+
+fn default_response_cache_size() -> usize {
+    256
+}
+
+fn default_seed_empty_db() -> bool {
+    cfg!(feature = "embedded")
+}
+
+fn default_import_conflicts_path() -> String {
+    String::from("./import-conflicts.json")
+}
+
+fn default_tags_path() -> String {
+    String::from("../quarry/tags.md")
+}
+
+fn default_quarry_csv_dir() -> String {
+    String::from("../quarry")
+}
+
+fn default_items_schema_path() -> String {
+    String::from("../quarry/jsonschemas/item-template.jsonschema")
+}
+
+fn default_abilities_schema_path() -> String {
+    String::from("../quarry/jsonschemas/spell-template.jsonschema")
+}
+
+fn default_bind_address() -> String {
+    String::from("0.0.0.0")
+}
+
+fn default_port() -> u16 {
+    8000
+}
+
+fn default_oidc_issuer() -> String {
+    String::from("https://accounts.google.com")
+}
+
+fn default_oidc_jwks_url() -> String {
+    String::from("https://www.googleapis.com/oauth2/v3/certs")
+}
+
+fn default_access_token_lifetime_seconds() -> u64 {
+    15 * 60
+}
+
+fn default_refresh_token_lifetime_seconds() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+/// What the import does with records that do not match the JSON Schema.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Strictness {
+    /// Leaves the invalid records out and imports the rest.
+    Skip,
+    /// Aborts the import if any record is invalid.
+    #[default]
+    Reject,
+}
+
+impl Strictness {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Strictness::Skip => "skip",
+            Strictness::Reject => "reject",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Config {
+    #[serde(default = "default_abilities_path")]
     pub(crate) abilities_path: String,
+    #[serde(default = "default_items_path")]
     pub(crate) items_path: String,
+    #[serde(default = "default_db_path")]
     pub(crate) db_path: String,
+    #[serde(default = "default_db_migrations")]
     pub(crate) db_migrations: String,
     pub(crate) auth_secret: String,
+    /// Where the import writes the changes it could not apply automatically.
+    #[serde(default = "default_import_conflicts_path")]
+    pub(crate) import_conflicts_path: String,
+    /// The tag vocabulary, one `<number>: <tag>` per line.
+    #[serde(default = "default_tags_path")]
+    pub(crate) tags_path: String,
+    /// Where the quarry CSV exports are read from.
+    #[serde(default = "default_quarry_csv_dir")]
+    pub(crate) quarry_csv_dir: String,
+    /// A directory with the `.gamedatabundle` and `.stringtable` files of the game.
+    #[serde(default)]
+    pub(crate) game_data_dir: Option<String>,
+    #[serde(default = "default_items_schema_path")]
+    pub(crate) items_schema_path: String,
+    #[serde(default = "default_abilities_schema_path")]
+    pub(crate) abilities_schema_path: String,
+    #[serde(default)]
+    pub(crate) import_strictness: Strictness,
+    #[serde(default = "default_bind_address")]
+    pub(crate) bind_address: String,
+    #[serde(default = "default_port")]
+    pub(crate) port: u16,
+    /// A PEM certificate chain. The server only serves HTTPS if both TLS paths are set.
+    #[serde(default)]
+    pub(crate) tls_cert_path: Option<String>,
+    /// The PEM private key of the certificate.
+    #[serde(default)]
+    pub(crate) tls_key_path: Option<String>,
+    /// Listens on this Unix domain socket instead of the bind address and port, without TLS.
+    /// A socket left at the path by a previous run is replaced, any other file is not.
+    #[serde(default)]
+    pub(crate) unix_socket_path: Option<String>,
+    /// Imports the quarry output when the server starts with no items and abilities.
+    /// Defaults to true in binaries with the embedded resources.
+    #[serde(default = "default_seed_empty_db")]
+    pub(crate) seed_empty_db: bool,
+    /// How long a query waits for another writer before failing.
+    #[serde(default = "default_db_busy_timeout_ms")]
+    pub(crate) db_busy_timeout_ms: u64,
+    /// The number of connections the server keeps open.
+    #[serde(default = "default_db_pool_size")]
+    pub(crate) db_pool_size: u32,
+    /// Answers `/api/indexed` from an in-memory index of the tags built at startup.
+    #[serde(default)]
+    pub(crate) tag_index: bool,
+    /// How many responses of the read-only endpoints the server caches, 0 to disable the cache.
+    /// Only the writes through the server invalidate it: restart the server after running a
+    /// command that writes to its database.
+    #[serde(default = "default_response_cache_size")]
+    pub(crate) response_cache_size: usize,
+    /// The OAuth client ID the ID tokens of a login must be issued for.
+    #[serde(default)]
+    pub(crate) oidc_audience: Option<String>,
+    /// The `iss` of the ID tokens of a login.
+    #[serde(default = "default_oidc_issuer")]
+    pub(crate) oidc_issuer: String,
+    /// Where the keys that sign the ID tokens are fetched from.
+    #[serde(default = "default_oidc_jwks_url")]
+    pub(crate) oidc_jwks_url: String,
+    /// Reads the keys from this JWKS file instead of fetching them, e.g. to test offline.
+    /// The file is read once, at the first login.
+    #[serde(default)]
+    pub(crate) oidc_jwks_path: Option<String>,
+    /// How long the access tokens of the API are valid.
+    #[serde(default = "default_access_token_lifetime_seconds")]
+    pub(crate) access_token_lifetime_seconds: u64,
+    /// How long a refresh token can be exchanged for a new access token.
+    #[serde(default = "default_refresh_token_lifetime_seconds")]
+    pub(crate) refresh_token_lifetime_seconds: u64,
 }
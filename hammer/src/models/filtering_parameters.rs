@@ -1,3 +1,5 @@
+use crate::error::Error;
+use crate::models::SourceFilter;
 use serde::{Deserialize, Serialize};
 
 fn default_filter_logic() -> String {
@@ -18,4 +20,25 @@ pub(crate) struct FilterParams {
     pub(crate) tags: Vec<String>, // comma-separated list
     #[serde(default = "default_filter_logic")]
     pub(crate) filter_logic: String, // "and" or "or"
+    #[serde(default)]
+    pub(crate) verified: Option<bool>,
+    #[serde(default)]
+    pub(crate) source: Option<SourceFilter>, // "human" or "import"
+    /// Leaves out the entities with any of these tags, whatever their source.
+    #[serde(default)]
+    pub(crate) exclude_tags: Vec<String>,
+}
+
+impl FilterParams {
+    /// Whether an entity needs all of the tags rather than any of them.
+    pub(crate) fn match_all(&self) -> Result<bool, Error> {
+        match self.filter_logic.as_str() {
+            "or" => Ok(false),
+            "and" => Ok(true),
+            filter_logic => {
+                tracing::warn!("Unsupported filter logic: {filter_logic}");
+                Err(format!("Unsupported filter logic {filter_logic}").into())
+            }
+        }
+    }
 }
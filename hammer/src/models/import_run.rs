@@ -0,0 +1,125 @@
+use crate::{error::Error, models::IndexedEntityType};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct SyncCounts {
+    pub(crate) created: usize,
+    pub(crate) updated: usize,
+    pub(crate) unchanged: usize,
+    pub(crate) removed: usize,
+}
+
+impl Display for SyncCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} unchanged, {} removed",
+            self.created, self.updated, self.unchanged, self.removed
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportRun {
+    pub(crate) id: i64,
+    pub(crate) items_path: String,
+    pub(crate) items_hash: String,
+    pub(crate) abilities_path: String,
+    pub(crate) abilities_hash: String,
+    pub(crate) items: SyncCounts,
+    pub(crate) abilities: SyncCounts,
+    pub(crate) conflicts: usize,
+    pub(crate) started_at: String,
+    pub(crate) finished_at: Option<String>,
+    pub(crate) rolled_back_at: Option<String>,
+}
+
+impl Display for ImportRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Run {} started at {}, finished at {}{}",
+            self.id,
+            self.started_at,
+            self.finished_at.as_deref().unwrap_or("-"),
+            self.rolled_back_at
+                .as_ref()
+                .map(|at| format!(", rolled back at {at}"))
+                .unwrap_or_default()
+        )?;
+        writeln!(
+            f,
+            "  Items ({}, {}): {}",
+            self.items_path, self.items_hash, self.items
+        )?;
+        writeln!(
+            f,
+            "  Abilities ({}, {}): {}",
+            self.abilities_path, self.abilities_hash, self.abilities
+        )?;
+        write!(f, "  Conflicts: {}", self.conflicts)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ChangeAction {
+    Created,
+    Updated,
+    Removed,
+}
+
+impl ChangeAction {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ChangeAction::Created => "created",
+            ChangeAction::Updated => "updated",
+            ChangeAction::Removed => "removed",
+        }
+    }
+}
+
+impl FromStr for ChangeAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(ChangeAction::Created),
+            "updated" => Ok(ChangeAction::Updated),
+            "removed" => Ok(ChangeAction::Removed),
+            _ => Err(format!("Unknown change action: {s}").into()),
+        }
+    }
+}
+
+/// A change an import run made to an entity, with JSON snapshots of the entity.
+#[derive(Debug)]
+pub(crate) struct ImportRunChange {
+    pub(crate) entity_type: IndexedEntityType,
+    pub(crate) slug: String,
+    pub(crate) action: ChangeAction,
+    pub(crate) before: Option<String>,
+    pub(crate) after: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct RollbackSummary {
+    pub(crate) reverted: usize,
+    /// Entities that changed after the run and were left as they are.
+    pub(crate) skipped: Vec<String>,
+}
+
+impl Display for RollbackSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Reverted {} changes", self.reverted)?;
+        if !self.skipped.is_empty() {
+            write!(
+                f,
+                "\nSkipped entities changed after the run: {}",
+                self.skipped.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
-use crate::models::{PersistedAbbreviatedAbility, PersistedItem};
+use crate::{
+    error::Error,
+    models::{PersistedAbbreviatedAbility, PersistedItem},
+};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Hash)]
 pub(crate) struct IndexedEntity {
@@ -7,11 +11,12 @@ pub(crate) struct IndexedEntity {
     pub(crate) slug: String,
     pub(crate) wiki_url: String,
     pub(crate) tags: Vec<String>,
+    pub(crate) verified: bool,
     #[serde(rename = "type")]
     pub(crate) entity_type: IndexedEntityType,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Hash)]
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Hash)]
 pub(crate) enum IndexedEntityType {
     #[serde(rename = "item")]
     Item,
@@ -19,6 +24,27 @@ pub(crate) enum IndexedEntityType {
     Ability,
 }
 
+impl IndexedEntityType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            IndexedEntityType::Item => "item",
+            IndexedEntityType::Ability => "ability",
+        }
+    }
+}
+
+impl FromStr for IndexedEntityType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "item" => Ok(IndexedEntityType::Item),
+            "ability" => Ok(IndexedEntityType::Ability),
+            _ => Err(format!("Unknown entity type: {s}").into()),
+        }
+    }
+}
+
 impl From<PersistedItem> for IndexedEntity {
     fn from(value: PersistedItem) -> Self {
         Self {
@@ -26,6 +52,7 @@ impl From<PersistedItem> for IndexedEntity {
             slug: value.slug,
             wiki_url: value.wiki_url,
             tags: value.tags,
+            verified: value.verified,
             entity_type: IndexedEntityType::Item,
         }
     }
@@ -38,6 +65,7 @@ impl From<PersistedAbbreviatedAbility> for IndexedEntity {
             slug: value.slug,
             wiki_url: value.wiki_url,
             tags: value.tags,
+            verified: value.verified,
             entity_type: IndexedEntityType::Ability,
         }
     }
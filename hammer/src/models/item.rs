@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+/// Fields of an item that editors can lock against changes from imports.
+pub(crate) const ITEM_LOCKABLE_FIELDS: [&str; 4] =
+    ["name", "wiki_url", "effects_description", "tags"];
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct Item {
     pub(crate) name: String,
@@ -17,6 +21,9 @@ pub(crate) struct PersistedItem {
     pub(crate) wiki_url: String,
     pub(crate) tags: Vec<String>,
     pub(crate) effects_description: String,
+    pub(crate) verified: bool,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
 }
 
 impl From<PersistedItem> for Item {
@@ -1,15 +1,39 @@
 mod abbreviated_ability;
 mod ability;
+mod change_event;
+mod changes;
 mod config;
 mod filtering_parameters;
+mod import_run;
 mod indexed_entity;
 mod item;
 mod tag;
+mod tag_proposal;
+mod tag_source;
+mod timestamped;
+mod trashed_entity;
+mod verification;
+mod webhook;
 
-pub(crate) use abbreviated_ability::{AbbreviatedAbility, PersistedAbbreviatedAbility};
+pub(crate) use abbreviated_ability::{
+    ABILITY_LOCKABLE_FIELDS, AbbreviatedAbility, PersistedAbbreviatedAbility,
+};
 pub(crate) use ability::Ability;
-pub(crate) use config::CONFIG;
+pub(crate) use change_event::{ChangeEvent, changed_fields};
+pub(crate) use changes::{Changes, DeletedEntity};
+pub(crate) use config::{CONFIG, ConfigOverrides, Strictness, override_config};
 pub(crate) use filtering_parameters::FilterParams;
-pub(crate) use indexed_entity::IndexedEntity;
-pub(crate) use item::{Item, JsonItem, PersistedItem};
+pub(crate) use import_run::{
+    ChangeAction, ImportRun, ImportRunChange, RollbackSummary, SyncCounts,
+};
+pub(crate) use indexed_entity::{IndexedEntity, IndexedEntityType};
+pub(crate) use item::{ITEM_LOCKABLE_FIELDS, Item, JsonItem, PersistedItem};
 pub(crate) use tag::Tag;
+pub(crate) use tag_proposal::{NewTagProposal, ProposalStatus, TagProposal};
+pub(crate) use tag_source::{EntityTag, SourceFilter, TagSource};
+pub(crate) use timestamped::Timestamped;
+pub(crate) use trashed_entity::TrashedEntity;
+pub(crate) use verification::Verification;
+pub(crate) use webhook::{
+    DeliveryStatus, NewWebhook, PendingDelivery, Webhook, WebhookDelivery, WebhookPayload,
+};
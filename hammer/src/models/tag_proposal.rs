@@ -0,0 +1,104 @@
+use crate::{error::Error, models::IndexedEntityType};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NewTagProposal {
+    #[serde(rename = "type")]
+    pub(crate) entity_type: IndexedEntityType,
+    pub(crate) slug: String,
+    #[serde(default)]
+    pub(crate) add: Vec<String>,
+    #[serde(default)]
+    pub(crate) remove: Vec<String>,
+    pub(crate) comment: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TagProposal {
+    pub(crate) id: i64,
+    #[serde(rename = "type")]
+    pub(crate) entity_type: IndexedEntityType,
+    pub(crate) slug: String,
+    pub(crate) add: Vec<String>,
+    pub(crate) remove: Vec<String>,
+    pub(crate) comment: Option<String>,
+    pub(crate) author_email: String,
+    pub(crate) status: ProposalStatus,
+    pub(crate) reviewer_email: Option<String>,
+    pub(crate) created_at: String,
+    pub(crate) reviewed_at: Option<String>,
+}
+
+impl TagProposal {
+    /// Applies the proposed changes to the current tags of the entity.
+    pub(crate) fn apply_to(&self, current_tags: Vec<String>) -> Vec<String> {
+        let mut tags: Vec<String> = current_tags
+            .into_iter()
+            .filter(|tag| !self.remove.contains(tag))
+            .collect();
+        for tag in &self.add {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        tags
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ProposalStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "accepted")]
+    Accepted,
+    #[serde(rename = "rejected")]
+    Rejected,
+}
+
+impl ProposalStatus {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Accepted => "accepted",
+            ProposalStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromStr for ProposalStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ProposalStatus::Pending),
+            "accepted" => Ok(ProposalStatus::Accepted),
+            "rejected" => Ok(ProposalStatus::Rejected),
+            _ => Err(format!("Unknown proposal status: {s}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_to() {
+        let proposal = TagProposal {
+            id: 1,
+            entity_type: IndexedEntityType::Item,
+            slug: "drawn-in-spring".to_string(),
+            add: vec!["freeze".to_string(), "burn".to_string()],
+            remove: vec!["shock".to_string()],
+            comment: None,
+            author_email: "viewer@example.com".to_string(),
+            status: ProposalStatus::Pending,
+            reviewer_email: None,
+            created_at: String::new(),
+            reviewed_at: None,
+        };
+        let tags = proposal.apply_to(vec!["shock".to_string(), "freeze".to_string()]);
+        assert_eq!(tags, vec!["freeze".to_string(), "burn".to_string()]);
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Where an entity-tag pair came from.
+#[derive(Debug, Clone)]
+pub(crate) enum TagSource {
+    /// Added by a quarry import run.
+    Import(String),
+    /// Added by an editor, identified by their email.
+    Editor(String),
+    /// Added by accepting the tag proposal with the given id.
+    Proposal(i64),
+}
+
+impl TagSource {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            TagSource::Import(_) => "import",
+            TagSource::Editor(_) => "editor",
+            TagSource::Proposal(_) => "proposal",
+        }
+    }
+
+    pub(crate) fn reference(&self) -> String {
+        match self {
+            TagSource::Import(run) => run.clone(),
+            TagSource::Editor(email) => email.clone(),
+            TagSource::Proposal(id) => id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EntityTag {
+    pub(crate) name: String,
+    pub(crate) source: String,
+    pub(crate) source_ref: Option<String>,
+}
+
+/// Filters entities by the provenance of their tags.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub(crate) enum SourceFilter {
+    /// Tags added by editors or through accepted proposals.
+    #[serde(rename = "human")]
+    Human,
+    #[serde(rename = "import")]
+    Import,
+}
@@ -0,0 +1,36 @@
+use crate::models::{AbbreviatedAbility, Item, PersistedAbbreviatedAbility, PersistedItem};
+use serde::Serialize;
+
+/// An entity as the API returns it, with the UTC times it was created and last changed in
+/// RFC 3339 format. A tag change counts as a change of the entity.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Timestamped<T> {
+    #[serde(flatten)]
+    pub(crate) entity: T,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
+}
+
+impl From<PersistedItem> for Timestamped<Item> {
+    fn from(value: PersistedItem) -> Self {
+        let created_at = value.created_at.clone();
+        let updated_at = value.updated_at.clone();
+        Self {
+            entity: value.into(),
+            created_at,
+            updated_at,
+        }
+    }
+}
+
+impl From<PersistedAbbreviatedAbility> for Timestamped<AbbreviatedAbility> {
+    fn from(value: PersistedAbbreviatedAbility) -> Self {
+        let created_at = value.created_at.clone();
+        let updated_at = value.updated_at.clone();
+        Self {
+            entity: value.into(),
+            created_at,
+            updated_at,
+        }
+    }
+}
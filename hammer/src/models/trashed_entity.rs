@@ -0,0 +1,11 @@
+use crate::models::IndexedEntityType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TrashedEntity {
+    pub(crate) name: String,
+    pub(crate) slug: String,
+    pub(crate) deleted_at: String,
+    #[serde(rename = "type")]
+    pub(crate) entity_type: IndexedEntityType,
+}
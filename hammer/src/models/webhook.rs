@@ -0,0 +1,78 @@
+use crate::{error::Error, models::ChangeEvent};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct NewWebhook {
+    pub(crate) url: String,
+    /// The key of the HMAC-SHA256 signature of the payloads.
+    pub(crate) secret: String,
+}
+
+/// A webhook as the API returns it. The secret is never returned.
+#[derive(Debug, Serialize)]
+pub(crate) struct Webhook {
+    pub(crate) id: i64,
+    pub(crate) url: String,
+    pub(crate) created_by: String,
+    pub(crate) created_at: String,
+}
+
+/// The JSON body a webhook receives for every change.
+#[derive(Debug, Serialize)]
+pub(crate) struct WebhookPayload<'a> {
+    #[serde(flatten)]
+    pub(crate) event: &'a ChangeEvent,
+    pub(crate) occurred_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct WebhookDelivery {
+    pub(crate) id: i64,
+    pub(crate) webhook_id: i64,
+    pub(crate) payload: String,
+    pub(crate) status: DeliveryStatus,
+    pub(crate) attempts: u32,
+    /// The HTTP status of the last attempt, if the endpoint answered.
+    pub(crate) response_status: Option<u16>,
+    /// Why the last attempt failed.
+    pub(crate) error: Option<String>,
+    pub(crate) created_at: String,
+    pub(crate) next_attempt_at: Option<String>,
+    pub(crate) finished_at: Option<String>,
+}
+
+/// A delivery that is due, with what the worker needs to send it.
+#[derive(Debug)]
+pub(crate) struct PendingDelivery {
+    pub(crate) id: i64,
+    pub(crate) url: String,
+    pub(crate) secret: String,
+    pub(crate) payload: String,
+    pub(crate) attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum DeliveryStatus {
+    /// Not delivered yet, and will be attempted again.
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "delivered")]
+    Delivered,
+    /// Gave up after the last attempt.
+    #[serde(rename = "failed")]
+    Failed,
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            _ => Err(format!("Unknown delivery status: {s}").into()),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+#[cfg(feature = "embedded")]
+include!(concat!(env!("OUT_DIR"), "/embedded_migrations.rs"));
+
+/// Files that the `embedded` feature compiles into the binary. A file at the configured path
+/// always takes precedence over the embedded copy.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Resource {
+    Items,
+    Abilities,
+    ItemsSchema,
+    AbilitiesSchema,
+    Tags,
+}
+
+impl Resource {
+    #[cfg(feature = "embedded")]
+    pub(crate) fn embedded(self) -> Option<&'static str> {
+        Some(match self {
+            Resource::Items => include_str!("../items.json"),
+            Resource::Abilities => include_str!("../abilities.json"),
+            Resource::ItemsSchema => {
+                include_str!("../../quarry/jsonschemas/item-template.jsonschema")
+            }
+            Resource::AbilitiesSchema => {
+                include_str!("../../quarry/jsonschemas/spell-template.jsonschema")
+            }
+            Resource::Tags => include_str!("../../quarry/tags.md"),
+        })
+    }
+
+    #[cfg(not(feature = "embedded"))]
+    pub(crate) fn embedded(self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Reads the file at `path`, or the embedded copy of the resource if there is no such file.
+pub(crate) fn read_to_string(path: &str, resource: Resource) -> std::io::Result<String> {
+    if !Path::new(path).exists()
+        && let Some(contents) = resource.embedded()
+    {
+        tracing::debug!("{path} does not exist, using the embedded {resource:?}");
+        return Ok(contents.to_string());
+    }
+    std::fs::read_to_string(path)
+}
+
+/// The names and contents of the embedded migrations, empty without the `embedded` feature.
+pub(crate) fn embedded_migrations() -> &'static [(&'static str, &'static str)] {
+    #[cfg(feature = "embedded")]
+    return EMBEDDED_MIGRATIONS;
+    #[cfg(not(feature = "embedded"))]
+    return &[];
+}
@@ -1,54 +1,111 @@
-use crate::db;
+use super::etag::{self, IfMatch};
+use super::events;
+use crate::auth::User;
+use crate::db::{self, Pool};
 use crate::error::{Error, ErrorType};
-use axum::Json;
-use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::{Extension, Json};
+use rusqlite::{Connection, TransactionBehavior};
 
-use crate::models::AbbreviatedAbility;
+use crate::models::{
+    ABILITY_LOCKABLE_FIELDS, AbbreviatedAbility, ChangeAction, EntityTag, IndexedEntityType,
+    TagSource, Timestamped, Verification, changed_fields,
+};
+
+/// The ability as `GET /abilities/{slug}` returns it, which is what its ETag is computed from.
+fn find_representation(
+    slug: &str,
+    conn: &Connection,
+) -> Result<Option<Timestamped<AbbreviatedAbility>>, Error> {
+    Ok(db::ability::find_by_slug(slug, conn)?.map(Timestamped::from))
+}
 
 #[axum::debug_handler]
-pub(super) async fn delete(Path(slug): Path<String>) -> StatusCode {
-    match db::ability::delete_abbreviated_ability_by_slug(&slug) {
-        Ok(_) => {
-            tracing::event!(tracing::Level::DEBUG, "Deleted abbreviated ability: {slug}");
-            StatusCode::NO_CONTENT
-        }
-        Err(e) => {
-            tracing::event!(
-                tracing::Level::ERROR,
-                "Failed to delete abbreviated ability: {e}"
-            );
-            StatusCode::BAD_REQUEST
-        }
-    }
+pub(super) async fn delete(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        if_match.write(
+            conn,
+            |conn| find_representation(&slug, conn),
+            |tx| {
+                if !db::ability::delete(&slug, tx)? {
+                    return Err(Error("Ability not found".to_string(), ErrorType::NotFound));
+                }
+                events::record(
+                    IndexedEntityType::Ability,
+                    slug.clone(),
+                    ChangeAction::Removed,
+                    vec![],
+                    user.email,
+                    tx,
+                )
+            },
+        )
+    })
+    .await?;
+    tracing::debug!("Deleted abbreviated ability: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
 pub(super) async fn update(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
     Path(slug): Path<String>,
+    if_match: IfMatch,
     Json(ability): Json<AbbreviatedAbility>,
-) -> StatusCode {
-    match db::ability::update_abbreviated_ability_by_slug(&slug, ability) {
-        Ok(_) => {
-            tracing::event!(tracing::Level::DEBUG, "Updated abbreviated ability: {slug}");
-            StatusCode::NO_CONTENT
-        }
-        Err(e) => {
-            tracing::event!(
-                tracing::Level::ERROR,
-                "Failed to update abbreviated ability: {e:?}"
-            );
-            StatusCode::BAD_REQUEST
-        }
-    }
+) -> Result<StatusCode, Error> {
+    let source = TagSource::Editor(user.email.clone());
+    let event = db::run(&pool, move |conn| {
+        if_match.write(
+            conn,
+            |conn| find_representation(&slug, conn),
+            |tx| {
+                // Editors rename abilities, the slug always follows the name
+                let ability = AbbreviatedAbility {
+                    slug: slug::slugify(&ability.name),
+                    ..ability
+                };
+                let before = db::ability::find_by_slug(&slug, tx)?.map(AbbreviatedAbility::from);
+                let fields = match before {
+                    Some(before) => changed_fields(&before, &ability)?,
+                    None => vec![],
+                };
+                db::ability::update_abbreviated_ability_in_transaction(
+                    &slug, ability, &source, tx,
+                )?;
+                events::record(
+                    IndexedEntityType::Ability,
+                    slug.clone(),
+                    ChangeAction::Updated,
+                    fields,
+                    user.email,
+                    tx,
+                )
+            },
+        )
+    })
+    .await?;
+    tracing::debug!("Updated abbreviated ability: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
-pub(super) async fn find_all() -> Result<Json<Vec<AbbreviatedAbility>>, Error> {
-    let conn = db::get_connection()?;
-    let abilities = db::ability::find_all(&conn)?
+pub(super) async fn find_all(
+    State(pool): State<Pool>,
+) -> Result<Json<Vec<Timestamped<AbbreviatedAbility>>>, Error> {
+    let abilities = db::run(&pool, |conn| db::ability::find_all(conn))
+        .await?
         .into_iter()
-        .map(AbbreviatedAbility::from)
+        .map(Timestamped::from)
         .collect();
     Ok(Json(abilities))
 }
@@ -56,27 +113,144 @@ pub(super) async fn find_all() -> Result<Json<Vec<AbbreviatedAbility>>, Error> {
 #[axum::debug_handler]
 #[tracing::instrument(level = "trace")]
 pub(super) async fn update_tags(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
     Path(slug): Path<String>,
+    if_match: IfMatch,
     Json(new_tags): Json<Vec<String>>,
 ) -> Result<Json<Vec<String>>, Error> {
-    let mut conn = db::get_connection()?;
-    let new_tags =
-        db::ability::update_tags_by_slug(&slug, new_tags, &mut conn).inspect_err(|err| {
-            tracing::warn!("Error when updating the tags of the ability {slug}. Error: {err:?}")
-        })?;
+    let source = TagSource::Editor(user.email.clone());
+    let (new_tags, event) = db::run(&pool, move |conn| {
+        let event = if_match
+            .write(
+                conn,
+                |conn| find_representation(&slug, conn),
+                |tx| {
+                    db::ability::update_tags_in_transaction(&slug, &new_tags, &source, tx)?;
+                    events::record(
+                        IndexedEntityType::Ability,
+                        slug.clone(),
+                        ChangeAction::Updated,
+                        vec!["tags".to_string()],
+                        user.email,
+                        tx,
+                    )
+                },
+            )
+            .inspect_err(|err| {
+                tracing::warn!("Error when updating the tags of the ability {slug}. Error: {err:?}")
+            })?;
+        Ok((new_tags, event))
+    })
+    .await?;
+    events::publish(event);
     Ok(Json(new_tags))
 }
 
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_tag_sources(
+    State(pool): State<Pool>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<EntityTag>>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| {
+            db::ability::find_tag_sources(&slug, conn)
+        })
+        .await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn set_verified(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    Json(verification): Json<Verification>,
+) -> Result<StatusCode, Error> {
+    let verified_by = verification.verified.then(|| user.email.clone());
+    let event = db::run(&pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if !db::ability::set_verified(&slug, verified_by.as_deref(), &tx)? {
+            return Err(Error("Ability not found".to_string(), ErrorType::NotFound));
+        }
+        let event = events::record(
+            IndexedEntityType::Ability,
+            slug,
+            ChangeAction::Updated,
+            vec!["verified".to_string()],
+            user.email,
+            &tx,
+        )?;
+        tx.commit()?;
+        Ok(event)
+    })
+    .await?;
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[axum::debug_handler]
 #[tracing::instrument(level = "trace")]
 pub(super) async fn find_by_slug(
+    State(pool): State<Pool>,
     Path(slug): Path<String>,
-) -> Result<Json<AbbreviatedAbility>, Error> {
-    let conn = db::get_connection()?;
-    let ability = db::ability::find_by_slug(&slug, &conn)
-        .inspect_err(|err| tracing::warn!("Error getting the ability with slug {slug}. {err:?}"))?;
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let ability = db::run(&pool, move |conn| {
+        find_representation(&slug, conn).inspect_err(|err| {
+            tracing::warn!("Error getting the ability with slug {slug}. {err:?}")
+        })
+    })
+    .await?;
     match ability {
-        Some(ability) => Ok(Json(ability.into())),
+        Some(ability) => etag::conditional_get(&headers, ability),
         None => Err(Error("Ability not found".to_string(), ErrorType::NotFound)),
     }
 }
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_locks(
+    State(pool): State<Pool>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<String>>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| db::ability::find_locks(&slug, conn)).await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn set_locks(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    Json(fields): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, Error> {
+    if let Some(field) = fields
+        .iter()
+        .find(|field| !ABILITY_LOCKABLE_FIELDS.contains(&field.as_str()))
+    {
+        return Err(format!("Field {field} of an ability cannot be locked").into());
+    }
+    let (locks, event) = db::run(&pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        db::ability::set_locks(&slug, &fields, &user.email, &tx)?;
+        let locks = db::ability::find_locks(&slug, &tx)?;
+        let event = events::record(
+            IndexedEntityType::Ability,
+            slug,
+            ChangeAction::Updated,
+            vec!["locks".to_string()],
+            user.email,
+            &tx,
+        )?;
+        tx.commit()?;
+        Ok((locks, event))
+    })
+    .await?;
+    events::publish(event);
+    Ok(Json(locks))
+}
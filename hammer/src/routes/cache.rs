@@ -0,0 +1,178 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    middleware::Next,
+    response::Response,
+};
+use lru::LruCache;
+
+use crate::error::Error;
+use crate::models::CONFIG;
+
+/// Counts the writes of this process. A cached response is only served while the generation it
+/// was stored in is the current one.
+///
+/// The writes of other processes on the same database, like the `import`, `sync-tags` or
+/// `migrate` commands, do not bump it, so the server must be restarted after them.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// `None` if the cache is disabled with a size of 0.
+static CACHE: LazyLock<Option<Mutex<LruCache<String, CachedResponse>>>> = LazyLock::new(|| {
+    NonZeroUsize::new(CONFIG.response_cache_size).map(|size| Mutex::new(LruCache::new(size)))
+});
+
+#[derive(Clone)]
+struct CachedResponse {
+    generation: u64,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Serves `GET` requests from the response cache, and caches the successful responses.
+pub(super) async fn cached(request: Request, next: Next) -> Result<Response, Error> {
+    let Some(cache) = CACHE.as_ref() else {
+        return Ok(next.run(request).await);
+    };
+    if request.method() != Method::GET {
+        return Ok(next.run(request).await);
+    }
+
+    let key = cache_key(request.uri());
+    // Read before the handler runs, so a response that raced with a write is already stale
+    let generation = GENERATION.load(Ordering::Acquire);
+    let hit = lock(cache)
+        .get(&key)
+        .filter(|cached| cached.generation == generation)
+        .cloned();
+    if let Some(cached) = hit {
+        let hits = HITS.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::debug!(
+            "Response cache hit for {key} ({hits} hits, {} misses)",
+            MISSES.load(Ordering::Relaxed)
+        );
+        let mut response = Response::new(Body::from(cached.body));
+        *response.headers_mut() = cached.headers;
+        return Ok(response);
+    }
+    let misses = MISSES.fetch_add(1, Ordering::Relaxed) + 1;
+    tracing::debug!(
+        "Response cache miss for {key} ({} hits, {misses} misses)",
+        HITS.load(Ordering::Relaxed)
+    );
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return Ok(response);
+    }
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|err| format!("Failed to read the response body: {err}"))?;
+    lock(cache).put(
+        key,
+        CachedResponse {
+            generation,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+        },
+    );
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Invalidates the cached responses after every request that can write, once the handler
+/// has committed its changes.
+pub(super) async fn bump_generation(request: Request, next: Next) -> Response {
+    let writes = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let response = next.run(request).await;
+    if writes {
+        GENERATION.fetch_add(1, Ordering::AcqRel);
+    }
+    response
+}
+
+/// The path with the query parameters in a canonical order.
+fn cache_key(uri: &Uri) -> String {
+    let mut params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .collect();
+    params.sort_unstable();
+    format!("{}?{}", uri.path(), params.join("&"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_ignores_the_parameter_order() {
+        let key = |uri: &str| cache_key(&uri.parse().unwrap());
+        assert_eq!(
+            key("/api/indexed?tags=b&filter_logic=and&tags=a"),
+            key("/api/indexed?filter_logic=and&tags=a&tags=b&")
+        );
+        assert_ne!(key("/api/indexed?tags=a"), key("/api/items?tags=a"));
+        assert_eq!(key("/api/tags"), "/api/tags?");
+    }
+
+    #[tokio::test]
+    async fn test_a_write_invalidates_the_cached_responses() {
+        use axum::{Router, handler::Handler, routing::get};
+        use tower::ServiceExt;
+
+        static CALLS: AtomicU64 = AtomicU64::new(0);
+        async fn read() -> ([(&'static str, &'static str); 1], String) {
+            let calls = CALLS.fetch_add(1, Ordering::Relaxed) + 1;
+            ([("x-calls", "counted")], calls.to_string())
+        }
+        let app = Router::new()
+            .route(
+                "/cached-for-test",
+                get(read.layer(axum::middleware::from_fn(cached))).post(|| async {}),
+            )
+            .layer(axum::middleware::from_fn(bump_generation));
+        let send = |method: Method| {
+            let request = Request::builder()
+                .method(method)
+                .uri("/cached-for-test")
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let header = response.headers().get("x-calls").cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (String::from_utf8(body.to_vec()).unwrap(), header)
+            }
+        };
+
+        let (first, _) = send(Method::GET).await;
+        let (hit, header) = send(Method::GET).await;
+        assert_eq!(hit, first);
+        assert_eq!(header.unwrap(), "counted");
+
+        let generation = GENERATION.load(Ordering::Acquire);
+        send(Method::POST).await;
+        assert!(GENERATION.load(Ordering::Acquire) > generation);
+        let (miss, _) = send(Method::GET).await;
+        assert_ne!(miss, first);
+    }
+}
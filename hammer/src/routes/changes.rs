@@ -0,0 +1,30 @@
+use crate::db::{self, Pool};
+use crate::error::Error;
+use crate::models::Changes;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ChangesParams {
+    /// The `next` of the previous response, or an RFC 3339 timestamp for the first request.
+    /// Everything if missing.
+    since: Option<String>,
+}
+
+/// Not behind the response cache, since the changes depend on the time of the request.
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_since(
+    State(pool): State<Pool>,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<Changes>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| {
+            db::changes::find_since(params.since.as_deref(), conn)
+        })
+        .await?,
+    ))
+}
@@ -0,0 +1,187 @@
+use std::convert::Infallible;
+
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, ErrorType};
+
+/// A strong ETag from a hash of the JSON representation of the resource.
+pub(super) fn etag<T: Serialize>(resource: &T) -> Result<String, Error> {
+    let json = serde_json::to_vec(resource).map_err(|err| err.to_string())?;
+    let hash: String = Sha256::digest(&json)[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Ok(format!("\"{hash}\""))
+}
+
+/// Responds with the resource and its ETag, or with 304 if `If-None-Match` has the ETag.
+pub(super) fn conditional_get<T: Serialize>(
+    headers: &HeaderMap,
+    resource: T,
+) -> Result<Response, Error> {
+    let etag = etag(&resource)?;
+    let not_modified = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|condition| matches(condition, &etag, true));
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(resource).into_response()
+    };
+    let etag = HeaderValue::from_str(&etag).map_err(|err| err.to_string())?;
+    response.headers_mut().insert(ETAG, etag);
+    Ok(response)
+}
+
+/// The `If-Match` header of a write, if there is one.
+#[derive(Debug, Clone)]
+pub(super) struct IfMatch(Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .headers
+                .get(IF_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        ))
+    }
+}
+
+impl IfMatch {
+    /// Runs the write in a transaction that first checks that the current representation of
+    /// the resource, `None` if it does not exist, still has one of the ETags of `If-Match`.
+    /// Fails with 412 Precondition Failed otherwise.
+    pub(super) fn write<R, T>(
+        &self,
+        conn: &mut Connection,
+        current: impl FnOnce(&Connection) -> Result<Option<R>, Error>,
+        write: impl FnOnce(&Transaction) -> Result<T, Error>,
+    ) -> Result<T, Error>
+    where
+        R: Serialize,
+    {
+        // Immediate, so no other writer can change the resource between the check and the write
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if let Some(condition) = &self.0 {
+            let matched = match current(&tx)? {
+                Some(resource) => matches(condition, &etag(&resource)?, false),
+                None => false,
+            };
+            if !matched {
+                return Err(Error(
+                    "The resource was changed since it was read".to_string(),
+                    ErrorType::PreconditionFailed,
+                ));
+            }
+        }
+        let result = write(&tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+}
+
+/// Whether the comma-separated list of ETags of a condition header has the ETag, or is `*`.
+/// The weak comparison of `If-None-Match` also accepts the weak form of the ETag.
+fn matches(condition: &str, etag: &str, weak: bool) -> bool {
+    condition.split(',').map(str::trim).any(|candidate| {
+        candidate == "*"
+            || candidate == etag
+            || (weak && candidate.strip_prefix("W/") == Some(etag))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::{Item, TagSource, Timestamped};
+
+    #[test]
+    fn test_conditions_match_the_etag() {
+        let etag = etag(&vec!["burn"]).unwrap();
+        assert_ne!(etag, super::etag(&vec!["freeze"]).unwrap());
+
+        assert!(matches(&format!("\"other\", {etag}"), &etag, false));
+        assert!(matches("*", &etag, false));
+        assert!(matches(&format!("W/{etag}"), &etag, true));
+        assert!(!matches(&format!("W/{etag}"), &etag, false));
+        assert!(!matches("\"other\"", &etag, true));
+    }
+
+    fn find_torch(conn: &Connection) -> Result<Option<Timestamped<Item>>, Error> {
+        Ok(db::item::find_by_slug("torch", conn)?.map(Timestamped::from))
+    }
+
+    fn torch_etag(conn: &Connection) -> String {
+        etag(&find_torch(conn).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_writes_with_a_stale_etag_are_not_applied() {
+        let mut conn = db::get_test_connection();
+        let source = TagSource::Editor("editor@example.com".to_string());
+        let torch = Item {
+            name: "Torch".to_string(),
+            slug: "torch".to_string(),
+            wiki_url: String::new(),
+            tags: vec!["freeze".to_string()],
+            effects_description: String::new(),
+        };
+        let tx = conn.transaction().unwrap();
+        db::item::insert_in_transaction(&torch, &source, &tx).unwrap();
+        tx.commit().unwrap();
+        let read = torch_etag(&conn);
+
+        let tags = vec!["burn".to_string()];
+        IfMatch(Some(read.clone()))
+            .write(&mut conn, find_torch, |tx| {
+                db::item::update_tags_in_transaction("torch", &tags, &source, tx)
+            })
+            .unwrap();
+        let current = torch_etag(&conn);
+        assert_ne!(current, read);
+
+        let renamed = Item {
+            name: "Lantern".to_string(),
+            ..torch
+        };
+        let err = IfMatch(Some(read.clone()))
+            .write(&mut conn, find_torch, |tx| {
+                db::item::update_in_transaction("torch", &renamed, &source, tx)
+            })
+            .unwrap_err();
+        assert!(matches!(err.1, ErrorType::PreconditionFailed));
+        let err = IfMatch(Some(read))
+            .write(&mut conn, find_torch, |tx| db::item::delete("torch", tx))
+            .unwrap_err();
+        assert!(matches!(err.1, ErrorType::PreconditionFailed));
+        assert_eq!(torch_etag(&conn), current);
+
+        assert!(
+            IfMatch(Some(current.clone()))
+                .write(&mut conn, find_torch, |tx| db::item::delete("torch", tx))
+                .unwrap()
+        );
+        let err = IfMatch(Some(current))
+            .write(&mut conn, find_torch, |tx| db::item::delete("torch", tx))
+            .unwrap_err();
+        assert!(matches!(err.1, ErrorType::PreconditionFailed));
+    }
+}
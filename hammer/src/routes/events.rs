@@ -0,0 +1,97 @@
+use std::sync::LazyLock;
+
+use axum::{
+    extract::Query,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use rusqlite::Transaction;
+use serde::Deserialize;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{BroadcastStream, WatchStream, errors::BroadcastStreamRecvError},
+};
+
+use crate::error::Error;
+use crate::models::{ChangeAction, ChangeEvent, IndexedEntityType};
+
+/// How many events a slow subscriber can fall behind before it misses some.
+const CAPACITY: usize = 256;
+
+static EVENTS: LazyLock<broadcast::Sender<ChangeEvent>> =
+    LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+/// Set on shutdown. The streams never end on their own, so the graceful shutdown would
+/// otherwise wait for the clients to disconnect.
+static CLOSED: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::channel(false).0);
+
+#[derive(Debug, Deserialize)]
+pub(super) struct EventsParams {
+    #[serde(rename = "type")]
+    entity_type: Option<IndexedEntityType>,
+    slug: Option<String>,
+}
+
+impl EventsParams {
+    fn matches(&self, event: &ChangeEvent) -> bool {
+        self.entity_type
+            .is_none_or(|entity_type| entity_type == event.entity_type)
+            && self.slug.as_ref().is_none_or(|slug| *slug == event.slug)
+    }
+}
+
+/// Queues the deliveries of a change to the webhooks as part of the transaction that makes it.
+/// Returns the event to [`publish`] once the transaction is committed.
+pub(super) fn record(
+    entity_type: IndexedEntityType,
+    slug: String,
+    action: ChangeAction,
+    fields: Vec<String>,
+    author: String,
+    tx: &Transaction,
+) -> Result<ChangeEvent, Error> {
+    let event = ChangeEvent {
+        entity_type,
+        slug,
+        action,
+        fields,
+        author,
+    };
+    crate::webhooks::enqueue(&event, tx)?;
+    Ok(event)
+}
+
+/// Broadcasts a committed change to the subscribers.
+pub(super) fn publish(event: ChangeEvent) {
+    tracing::debug!(
+        "Publishing {event:?} to {} subscribers",
+        EVENTS.receiver_count()
+    );
+    // Only fails if nobody is subscribed
+    let _ = EVENTS.send(event);
+}
+
+/// Ends the open event streams.
+pub(crate) fn close_streams() {
+    CLOSED.send_replace(true);
+}
+
+/// Streams a `change` event for every committed change matching the filter. A `lagged` event
+/// with the number of missed changes tells a client that fell behind to reload.
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn subscribe(
+    Query(params): Query<EventsParams>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = BroadcastStream::new(EVENTS.subscribe()).filter_map(move |event| match event {
+        Ok(event) if params.matches(&event) => {
+            Some(Some(Event::default().event("change").json_data(event)))
+        }
+        Ok(_) => None,
+        Err(BroadcastStreamRecvError::Lagged(missed)) => Some(Some(Ok(Event::default()
+            .event("lagged")
+            .data(missed.to_string())))),
+    });
+    let closed = WatchStream::from_changes(CLOSED.subscribe()).map(|_| None);
+    Sse::new(events.merge(closed).map_while(|event| event)).keep_alive(KeepAlive::default())
+}
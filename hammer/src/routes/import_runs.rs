@@ -0,0 +1,34 @@
+use super::events;
+use crate::auth::User;
+use crate::db::{self, Pool};
+use crate::error::Error;
+use crate::import_from_quarry::rollback_import_run;
+use crate::models::{ImportRun, RollbackSummary};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_all(State(pool): State<Pool>) -> Result<Json<Vec<ImportRun>>, Error> {
+    Ok(Json(
+        db::run(&pool, |conn| db::import_run::find_all(conn)).await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn rollback(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<Json<RollbackSummary>, Error> {
+    let (summary, changes) = db::run(&pool, move |conn| {
+        rollback_import_run(id, &user.email, conn)
+    })
+    .await?;
+    tracing::debug!("Rolled back import run {id}: {summary}");
+    changes.into_iter().for_each(events::publish);
+    Ok(Json(summary))
+}
@@ -1,65 +1,262 @@
-use crate::db::{self, item};
-use crate::error::Error;
-use crate::models::{Item, JsonItem};
+use super::etag::{self, IfMatch};
+use super::events;
+use crate::auth::User;
+use crate::db::{self, Pool, item};
+use crate::error::{Error, ErrorType};
+use crate::models::{
+    ChangeAction, EntityTag, ITEM_LOCKABLE_FIELDS, IndexedEntityType, Item, JsonItem, TagSource,
+    Timestamped, Verification, changed_fields,
+};
 use axum::{
-    Json,
-    extract::Path,
-    http::StatusCode,
+    Extension, Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
+use rusqlite::{Connection, TransactionBehavior};
+
+/// The item as `GET /items/{slug}` returns it, which is what its ETag is computed from.
+fn find_representation(slug: &str, conn: &Connection) -> Result<Option<Timestamped<Item>>, Error> {
+    Ok(item::find_by_slug(slug, conn)?.map(Timestamped::from))
+}
 
 #[axum::debug_handler]
-pub(super) async fn delete(Path(slug): Path<String>) -> Result<StatusCode, Error> {
-    let conn = db::get_connection()?;
-    item::delete(&slug, &conn)?;
+pub(super) async fn delete(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        if_match.write(
+            conn,
+            |conn| find_representation(&slug, conn),
+            |tx| {
+                if !item::delete(&slug, tx)? {
+                    return Err(Error("Item not found".to_string(), ErrorType::NotFound));
+                }
+                events::record(
+                    IndexedEntityType::Item,
+                    slug.clone(),
+                    ChangeAction::Removed,
+                    vec![],
+                    user.email,
+                    tx,
+                )
+            },
+        )
+    })
+    .await?;
+    events::publish(event);
     Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
 pub(super) async fn update(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
     Path(slug): Path<String>,
+    if_match: IfMatch,
     Json(item): Json<JsonItem>,
 ) -> Result<StatusCode, Error> {
-    let mut conn = db::get_connection()?;
-    item::update(&slug, &item.into(), &mut conn)?;
+    let source = TagSource::Editor(user.email.clone());
+    let event = db::run(&pool, move |conn| {
+        if_match.write(
+            conn,
+            |conn| find_representation(&slug, conn),
+            |tx| {
+                let item = Item::from(item);
+                let before = item::find_by_slug(&slug, tx)?.map(Item::from);
+                item::update_in_transaction(&slug, &item, &source, tx)?;
+                let fields = match before {
+                    Some(before) => changed_fields(&before, &item)?,
+                    None => vec![],
+                };
+                events::record(
+                    IndexedEntityType::Item,
+                    slug.clone(),
+                    ChangeAction::Updated,
+                    fields,
+                    user.email,
+                    tx,
+                )
+            },
+        )
+    })
+    .await?;
+    events::publish(event);
     Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
-pub(super) async fn find_all() -> Result<Json<Vec<Item>>, Error> {
-    let conn = db::get_connection()?;
-    Ok(Json(
-        item::find_all(&conn)?.into_iter().map(Item::from).collect(),
-    ))
+pub(super) async fn find_all(
+    State(pool): State<Pool>,
+) -> Result<Json<Vec<Timestamped<Item>>>, Error> {
+    let items = db::run(&pool, |conn| item::find_all(conn)).await?;
+    Ok(Json(items.into_iter().map(Timestamped::from).collect()))
 }
 
 #[axum::debug_handler]
-pub(super) async fn find_by_slug(Path(slug): Path<String>) -> Result<Response, Error> {
-    let conn = db::get_connection()?;
-    if let Some(item) = item::find_by_slug(&slug, &conn)? {
-        Ok((StatusCode::OK, Json(Item::from(item))).into_response())
+pub(super) async fn find_by_slug(
+    State(pool): State<Pool>,
+    Path(slug): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    if let Some(item) = db::run(&pool, move |conn| find_representation(&slug, conn)).await? {
+        etag::conditional_get(&headers, item)
     } else {
         Ok((StatusCode::NOT_FOUND).into_response())
     }
 }
 
 #[axum::debug_handler]
-pub(super) async fn insert(Json(item): Json<JsonItem>) -> Result<StatusCode, Error> {
-    let mut conn = db::get_connection()?;
+pub(super) async fn insert(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Json(item): Json<JsonItem>,
+) -> Result<StatusCode, Error> {
     let item = Item::from(item);
-    item::insert(&item, &mut conn)?;
+    let source = TagSource::Editor(user.email.clone());
+    let event = db::run(&pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        item::insert_in_transaction(&item, &source, &tx)?;
+        let event = events::record(
+            IndexedEntityType::Item,
+            item.slug,
+            ChangeAction::Created,
+            vec![],
+            user.email,
+            &tx,
+        )?;
+        tx.commit()?;
+        Ok(event)
+    })
+    .await?;
+    events::publish(event);
     Ok(StatusCode::CREATED)
 }
 
 #[axum::debug_handler]
 #[tracing::instrument(level = "trace")]
 pub(super) async fn update_tags(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
     Path(slug): Path<String>,
+    if_match: IfMatch,
     Json(new_tags): Json<Vec<String>>,
 ) -> Result<Json<Vec<String>>, Error> {
-    let mut conn = db::get_connection()?;
-    let new_tags = item::update_tags_by_slug(&slug, new_tags, &mut conn).inspect_err(|err| {
-        tracing::warn!("Error when trying to update tags for the item {slug}. Error: {err:?}")
-    })?;
+    let source = TagSource::Editor(user.email.clone());
+    let (new_tags, event) = db::run(&pool, move |conn| {
+        let event = if_match
+            .write(
+                conn,
+                |conn| find_representation(&slug, conn),
+                |tx| {
+                    item::update_tags_in_transaction(&slug, &new_tags, &source, tx)?;
+                    events::record(
+                        IndexedEntityType::Item,
+                        slug.clone(),
+                        ChangeAction::Updated,
+                        vec!["tags".to_string()],
+                        user.email,
+                        tx,
+                    )
+                },
+            )
+            .inspect_err(|err| {
+                tracing::warn!(
+                    "Error when trying to update tags for the item {slug}. Error: {err:?}"
+                )
+            })?;
+        Ok((new_tags, event))
+    })
+    .await?;
+    events::publish(event);
     Ok(Json(new_tags))
 }
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_tag_sources(
+    State(pool): State<Pool>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<EntityTag>>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| item::find_tag_sources(&slug, conn)).await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn set_verified(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    Json(verification): Json<Verification>,
+) -> Result<StatusCode, Error> {
+    let verified_by = verification.verified.then(|| user.email.clone());
+    let event = db::run(&pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if !item::set_verified(&slug, verified_by.as_deref(), &tx)? {
+            return Err(Error(format!("Item {slug} not found"), ErrorType::NotFound));
+        }
+        let event = events::record(
+            IndexedEntityType::Item,
+            slug,
+            ChangeAction::Updated,
+            vec!["verified".to_string()],
+            user.email,
+            &tx,
+        )?;
+        tx.commit()?;
+        Ok(event)
+    })
+    .await?;
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_locks(
+    State(pool): State<Pool>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<String>>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| item::find_locks(&slug, conn)).await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn set_locks(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+    Json(fields): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, Error> {
+    if let Some(field) = fields
+        .iter()
+        .find(|field| !ITEM_LOCKABLE_FIELDS.contains(&field.as_str()))
+    {
+        return Err(format!("Field {field} of an item cannot be locked").into());
+    }
+    let (locks, event) = db::run(&pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        item::set_locks(&slug, &fields, &user.email, &tx)?;
+        let locks = item::find_locks(&slug, &tx)?;
+        let event = events::record(
+            IndexedEntityType::Item,
+            slug,
+            ChangeAction::Updated,
+            vec!["locks".to_string()],
+            user.email,
+            &tx,
+        )?;
+        tx.commit()?;
+        Ok((locks, event))
+    })
+    .await?;
+    events::publish(event);
+    Ok(Json(locks))
+}
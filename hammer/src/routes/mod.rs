@@ -1,43 +1,142 @@
 mod abilities;
+mod cache;
+mod changes;
+mod etag;
+mod events;
+mod import_runs;
 mod indexed;
 mod items;
+mod proposals;
 mod tags;
+mod trash;
+mod webhooks;
 
-use crate::auth::auth_required;
+pub(crate) use events::close_streams;
+
+use crate::auth::{admin_required, auth_required, login_required};
+use crate::db::Pool;
 use axum::{
     Router,
     handler::Handler,
-    routing::{delete, get, patch},
+    middleware::from_fn_with_state,
+    routing::{delete, get, post, put},
 };
 
-pub(crate) fn get_backend_routes() -> Router<()> {
+pub(crate) fn get_backend_routes(pool: &Pool) -> Router<Pool> {
     Router::new()
-        .route("/indexed", get(indexed::get))
-        .route("/tags", get(tags::get))
-        .route("/abilities", get(abilities::find_all))
+        .route(
+            "/indexed",
+            get(indexed::get.layer(axum::middleware::from_fn(cache::cached))),
+        )
+        .route(
+            "/tags",
+            get(tags::get.layer(axum::middleware::from_fn(cache::cached))),
+        )
+        .route("/changes", get(changes::find_since))
+        .route("/events", get(events::subscribe))
+        .route(
+            "/abilities",
+            get(abilities::find_all.layer(axum::middleware::from_fn(cache::cached))),
+        )
         .route(
             "/abilities/{slug}",
-            delete(abilities::delete.layer(axum::middleware::from_fn(auth_required)))
-                .patch(abilities::update.layer(axum::middleware::from_fn(auth_required))),
+            delete(abilities::delete.layer(from_fn_with_state(pool.clone(), auth_required)))
+                .patch(abilities::update.layer(from_fn_with_state(pool.clone(), auth_required))),
         )
         .route("/abilities/{slug}", get(abilities::find_by_slug))
         .route(
             "/abilities/{slug}/tags",
-            patch(abilities::update_tags.layer(axum::middleware::from_fn(auth_required))),
+            get(abilities::find_tag_sources).patch(
+                abilities::update_tags.layer(from_fn_with_state(pool.clone(), auth_required)),
+            ),
+        )
+        .route(
+            "/abilities/{slug}/locks",
+            get(abilities::find_locks)
+                .put(abilities::set_locks.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/abilities/{slug}/verified",
+            put(abilities::set_verified.layer(from_fn_with_state(pool.clone(), auth_required))),
         )
         .route(
             "/items",
-            get(items::find_all)
-                .post(items::insert.layer(axum::middleware::from_fn(auth_required))),
+            get(items::find_all.layer(axum::middleware::from_fn(cache::cached)))
+                .post(items::insert.layer(from_fn_with_state(pool.clone(), auth_required))),
         )
         .route(
             "/items/{slug}",
             get(items::find_by_slug)
-                .patch(items::update.layer(axum::middleware::from_fn(auth_required)))
-                .delete(items::delete.layer(axum::middleware::from_fn(auth_required))),
+                .patch(items::update.layer(from_fn_with_state(pool.clone(), auth_required)))
+                .delete(items::delete.layer(from_fn_with_state(pool.clone(), auth_required))),
         )
         .route(
             "/items/{slug}/tags",
-            patch(items::update_tags.layer(axum::middleware::from_fn(auth_required))),
+            get(items::find_tag_sources)
+                .patch(items::update_tags.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/items/{slug}/locks",
+            get(items::find_locks)
+                .put(items::set_locks.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/items/{slug}/verified",
+            put(items::set_verified.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/proposals",
+            get(proposals::find_all.layer(from_fn_with_state(pool.clone(), auth_required)))
+                .post(proposals::submit.layer(from_fn_with_state(pool.clone(), login_required))),
+        )
+        .route(
+            "/proposals/{id}/accept",
+            post(proposals::accept.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/proposals/{id}/reject",
+            post(proposals::reject.layer(from_fn_with_state(pool.clone(), auth_required))),
+        )
+        .route(
+            "/trash",
+            get(trash::find_all.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/trash/items/{slug}",
+            delete(trash::purge_item.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/trash/items/{slug}/restore",
+            post(trash::restore_item.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/trash/abilities/{slug}",
+            delete(trash::purge_ability.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/trash/abilities/{slug}/restore",
+            post(trash::restore_ability.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/import-runs",
+            get(import_runs::find_all.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/import-runs/{id}/rollback",
+            post(import_runs::rollback.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/webhooks",
+            get(webhooks::find_all.layer(from_fn_with_state(pool.clone(), admin_required)))
+                .post(webhooks::insert.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/webhooks/{id}",
+            delete(webhooks::delete.layer(from_fn_with_state(pool.clone(), admin_required))),
+        )
+        .route(
+            "/webhooks/{id}/deliveries",
+            get(webhooks::find_deliveries.layer(from_fn_with_state(pool.clone(), admin_required))),
         )
+        .layer(axum::middleware::from_fn(cache::bump_generation))
 }
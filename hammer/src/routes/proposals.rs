@@ -0,0 +1,254 @@
+use super::events;
+use crate::auth::User;
+use crate::db::{self, Pool, tag_proposal};
+use crate::error::{Error, ErrorType};
+use crate::models::{
+    ChangeAction, ChangeEvent, IndexedEntityType, NewTagProposal, ProposalStatus, TagProposal,
+    TagSource,
+};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use rusqlite::{Connection, TransactionBehavior};
+use serde::Deserialize;
+
+fn default_status() -> ProposalStatus {
+    ProposalStatus::Pending
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ProposalsParams {
+    #[serde(default = "default_status")]
+    status: ProposalStatus,
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn submit(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Json(proposal): Json<NewTagProposal>,
+) -> Result<(StatusCode, Json<TagProposal>), Error> {
+    if proposal.add.is_empty() && proposal.remove.is_empty() {
+        return Err("The proposal does not change any tags".into());
+    }
+    let proposal = db::run(&pool, move |conn| {
+        find_current_tags(&proposal.entity_type, &proposal.slug, conn)?;
+        let id = tag_proposal::insert(&proposal, &user.email, conn)
+            .inspect_err(|err| tracing::warn!("Failed to insert the tag proposal. {err:?}"))?;
+        tag_proposal::find_by_id(id, conn)?.ok_or("Failed to read the proposal".into())
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(proposal)))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_all(
+    State(pool): State<Pool>,
+    Query(params): Query<ProposalsParams>,
+) -> Result<Json<Vec<TagProposal>>, Error> {
+    Ok(Json(
+        db::run(&pool, move |conn| {
+            tag_proposal::find_by_status(params.status, conn)
+        })
+        .await?,
+    ))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn accept(
+    State(pool): State<Pool>,
+    Extension(reviewer): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<Json<TagProposal>, Error> {
+    let (proposal, event) =
+        db::run(&pool, move |conn| accept_proposal(id, &reviewer, conn)).await?;
+    events::publish(event);
+    Ok(Json(proposal))
+}
+
+fn accept_proposal(
+    id: i64,
+    reviewer: &User,
+    conn: &mut Connection,
+) -> Result<(TagProposal, ChangeEvent), Error> {
+    // Marking the proposal accepted and applying its tags commit together or not at all
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let proposal = find_proposal(id, &tx)?;
+    if !tag_proposal::review(id, ProposalStatus::Accepted, &reviewer.email, &tx)? {
+        return Err(already_reviewed(id));
+    }
+
+    let current_tags = find_current_tags(&proposal.entity_type, &proposal.slug, &tx)?;
+    let new_tags = proposal.apply_to(current_tags);
+    let source = TagSource::Proposal(id);
+    match proposal.entity_type {
+        IndexedEntityType::Item => {
+            db::item::update_tags_in_transaction(&proposal.slug, &new_tags, &source, &tx)?
+        }
+        IndexedEntityType::Ability => {
+            db::ability::update_tags_in_transaction(&proposal.slug, &new_tags, &source, &tx)?
+        }
+    }
+    let event = events::record(
+        proposal.entity_type,
+        proposal.slug,
+        ChangeAction::Updated,
+        vec!["tags".to_string()],
+        reviewer.email.clone(),
+        &tx,
+    )?;
+    let proposal = find_proposal(id, &tx)?;
+    tx.commit()?;
+    tracing::debug!("Proposal {id} accepted by {}", reviewer.email);
+    Ok((proposal, event))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn reject(
+    State(pool): State<Pool>,
+    Extension(reviewer): Extension<User>,
+    Path(id): Path<i64>,
+) -> Result<Json<TagProposal>, Error> {
+    let proposal = db::run(&pool, move |conn| reject_proposal(id, &reviewer, conn)).await?;
+    Ok(Json(proposal))
+}
+
+fn reject_proposal(id: i64, reviewer: &User, conn: &Connection) -> Result<TagProposal, Error> {
+    find_proposal(id, conn)?;
+    if !tag_proposal::review(id, ProposalStatus::Rejected, &reviewer.email, conn)? {
+        return Err(already_reviewed(id));
+    }
+    tracing::debug!("Proposal {id} rejected by {}", reviewer.email);
+    find_proposal(id, conn)
+}
+
+fn find_proposal(id: i64, conn: &Connection) -> Result<TagProposal, Error> {
+    tag_proposal::find_by_id(id, conn)?
+        .ok_or_else(|| Error(format!("Proposal {id} not found"), ErrorType::NotFound))
+}
+
+fn find_current_tags(
+    entity_type: &IndexedEntityType,
+    slug: &str,
+    conn: &Connection,
+) -> Result<Vec<String>, Error> {
+    let tags = match entity_type {
+        IndexedEntityType::Item => db::item::find_by_slug(slug, conn)?.map(|item| item.tags),
+        IndexedEntityType::Ability => {
+            db::ability::find_by_slug(slug, conn)?.map(|ability| ability.tags)
+        }
+    };
+    tags.ok_or_else(|| {
+        Error(
+            format!("No {} with slug {slug}", entity_type.as_str()),
+            ErrorType::NotFound,
+        )
+    })
+}
+
+fn already_reviewed(id: i64) -> Error {
+    Error(
+        format!("Proposal {id} has already been reviewed"),
+        ErrorType::Conflict,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::models::Item;
+
+    fn reviewer() -> User {
+        User {
+            email: "reviewer@example.com".to_string(),
+            role: Role::Editor,
+        }
+    }
+
+    fn connection_with_torch() -> Connection {
+        let mut conn = db::get_test_connection();
+        let torch = Item {
+            name: "Torch".to_string(),
+            slug: "torch".to_string(),
+            wiki_url: String::new(),
+            tags: vec!["freeze".to_string()],
+            effects_description: String::new(),
+        };
+        let tx = conn.transaction().unwrap();
+        db::item::insert_in_transaction(&torch, &TagSource::Import("1".to_string()), &tx).unwrap();
+        tx.commit().unwrap();
+        conn
+    }
+
+    fn propose(conn: &mut Connection) -> i64 {
+        let proposal = NewTagProposal {
+            entity_type: IndexedEntityType::Item,
+            slug: "torch".to_string(),
+            add: vec!["burn".to_string()],
+            remove: vec!["freeze".to_string()],
+            comment: None,
+        };
+        tag_proposal::insert(&proposal, "author@example.com", conn).unwrap()
+    }
+
+    fn torch_tags(conn: &Connection) -> Vec<(String, String)> {
+        db::item::find_tag_sources("torch", conn)
+            .unwrap()
+            .into_iter()
+            .map(|tag| (tag.name, tag.source))
+            .collect()
+    }
+
+    #[test]
+    fn test_accepting_a_proposal_applies_its_tags_once() {
+        let mut conn = connection_with_torch();
+        let id = propose(&mut conn);
+
+        let (proposal, event) = accept_proposal(id, &reviewer(), &mut conn).unwrap();
+        assert!(matches!(proposal.status, ProposalStatus::Accepted));
+        assert_eq!(event.fields, ["tags"]);
+        assert_eq!(
+            torch_tags(&conn),
+            [("burn".to_string(), "proposal".to_string())]
+        );
+
+        let err = accept_proposal(id, &reviewer(), &mut conn).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict));
+        let err = reject_proposal(id, &reviewer(), &conn).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict));
+    }
+
+    #[test]
+    fn test_rejecting_a_proposal_leaves_the_tags_unchanged() {
+        let mut conn = connection_with_torch();
+        let id = propose(&mut conn);
+
+        let proposal = reject_proposal(id, &reviewer(), &conn).unwrap();
+        assert!(matches!(proposal.status, ProposalStatus::Rejected));
+        assert_eq!(
+            torch_tags(&conn),
+            [("freeze".to_string(), "import".to_string())]
+        );
+
+        let err = accept_proposal(id, &reviewer(), &mut conn).unwrap_err();
+        assert!(matches!(err.1, ErrorType::Conflict));
+        assert_eq!(
+            torch_tags(&conn),
+            [("freeze".to_string(), "import".to_string())]
+        );
+
+        let pending = propose(&mut conn);
+        let queue = tag_proposal::find_by_status(ProposalStatus::Pending, &conn).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].id, pending);
+        assert_eq!(queue[0].add, ["burn"]);
+        assert_eq!(queue[0].remove, ["freeze"]);
+    }
+}
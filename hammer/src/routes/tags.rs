@@ -1,14 +1,16 @@
 use crate::{
-    db::{self, tag},
+    db::{self, Pool, tag},
     error::Error,
     models::Tag,
 };
-use axum::Json;
+use axum::{Json, extract::State};
 
 #[axum::debug_handler]
 #[tracing::instrument(level = "trace")]
-pub(crate) async fn get() -> Result<Json<Vec<Tag>>, Error> {
-    Ok(Json(tag::find_all(&db::get_connection()?).inspect_err(
-        |err| tracing::warn!("Failed to fetch all tags. {err:?}"),
-    )?))
+pub(crate) async fn get(State(pool): State<Pool>) -> Result<Json<Vec<Tag>>, Error> {
+    Ok(Json(
+        db::run(&pool, |conn| tag::find_all(conn))
+            .await
+            .inspect_err(|err| tracing::warn!("Failed to fetch all tags. {err:?}"))?,
+    ))
 }
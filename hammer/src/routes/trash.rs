@@ -0,0 +1,138 @@
+use super::events;
+use crate::auth::User;
+use crate::db::{self, Pool};
+use crate::error::{Error, ErrorType};
+use crate::models::{ChangeAction, ChangeEvent, IndexedEntityType, TrashedEntity};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use rusqlite::{Connection, TransactionBehavior};
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_all(State(pool): State<Pool>) -> Result<Json<Vec<TrashedEntity>>, Error> {
+    let trash = db::run(&pool, |conn| {
+        let mut trash = db::item::find_deleted(conn)?;
+        trash.extend(db::ability::find_deleted(conn)?);
+        Ok(trash)
+    })
+    .await?;
+    Ok(Json(trash))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn restore_item(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        change_in_trash(
+            IndexedEntityType::Item,
+            slug,
+            ChangeAction::Created,
+            user.email,
+            db::item::restore,
+            conn,
+        )
+    })
+    .await?;
+    tracing::debug!("Restored item: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn purge_item(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        change_in_trash(
+            IndexedEntityType::Item,
+            slug,
+            ChangeAction::Removed,
+            user.email,
+            db::item::purge,
+            conn,
+        )
+    })
+    .await?;
+    tracing::debug!("Purged item: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn restore_ability(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        change_in_trash(
+            IndexedEntityType::Ability,
+            slug,
+            ChangeAction::Created,
+            user.email,
+            db::ability::restore,
+            conn,
+        )
+    })
+    .await?;
+    tracing::debug!("Restored ability: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn purge_ability(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, Error> {
+    let event = db::run(&pool, move |conn| {
+        change_in_trash(
+            IndexedEntityType::Ability,
+            slug,
+            ChangeAction::Removed,
+            user.email,
+            db::ability::purge,
+            conn,
+        )
+    })
+    .await?;
+    tracing::debug!("Purged ability: {}", event.slug);
+    events::publish(event);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restores or purges the entity in one transaction with the webhook deliveries of the change.
+/// `change` returns `false` if the entity is not in the trash.
+fn change_in_trash(
+    entity_type: IndexedEntityType,
+    slug: String,
+    action: ChangeAction,
+    author: String,
+    change: impl FnOnce(&str, &Connection) -> Result<bool, Error>,
+    conn: &mut Connection,
+) -> Result<ChangeEvent, Error> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if !change(&slug, &tx)? {
+        return Err(not_in_trash(&slug));
+    }
+    let event = events::record(entity_type, slug, action, vec![], author, &tx)?;
+    tx.commit()?;
+    Ok(event)
+}
+
+fn not_in_trash(slug: &str) -> Error {
+    Error(format!("{slug} is not in the trash"), ErrorType::NotFound)
+}
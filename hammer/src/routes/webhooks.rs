@@ -0,0 +1,72 @@
+use crate::auth::User;
+use crate::db::{self, Pool, webhook};
+use crate::error::{Error, ErrorType};
+use crate::models::{NewWebhook, Webhook, WebhookDelivery};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+
+/// How many of the latest deliveries the delivery log shows.
+const DELIVERY_LOG_SIZE: usize = 100;
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_all(State(pool): State<Pool>) -> Result<Json<Vec<Webhook>>, Error> {
+    Ok(Json(db::run(&pool, |conn| webhook::find_all(conn)).await?))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace", skip(webhook))]
+pub(super) async fn insert(
+    State(pool): State<Pool>,
+    Extension(user): Extension<User>,
+    Json(webhook): Json<NewWebhook>,
+) -> Result<(StatusCode, Json<Webhook>), Error> {
+    let url = reqwest::Url::parse(&webhook.url)
+        .map_err(|err| format!("{} is not a valid URL: {err}", webhook.url))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{} is not an HTTP URL", webhook.url).into());
+    }
+    if webhook.secret.is_empty() {
+        return Err("The secret of a webhook cannot be empty".into());
+    }
+    let webhook = db::run(&pool, move |conn| {
+        webhook::insert(&webhook, &user.email, conn)
+    })
+    .await?;
+    tracing::info!("Added the webhook {} to {}", webhook.id, webhook.url);
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn delete(
+    State(pool): State<Pool>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, Error> {
+    if db::run(&pool, move |conn| webhook::delete(id, conn)).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(not_found(id))
+    }
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(level = "trace")]
+pub(super) async fn find_deliveries(
+    State(pool): State<Pool>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<WebhookDelivery>>, Error> {
+    db::run(&pool, move |conn| {
+        webhook::find_deliveries(id, DELIVERY_LOG_SIZE, conn)
+    })
+    .await?
+    .map(Json)
+    .ok_or_else(|| not_found(id))
+}
+
+fn not_found(id: i64) -> Error {
+    Error(format!("Webhook {id} not found"), ErrorType::NotFound)
+}
@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+
+use crate::error::Error;
+use crate::models::CONFIG;
+use axum::Router;
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+
+/// Serves the app on the configured Unix socket, or on the bind address and port, with TLS
+/// if the certificate and key are configured. Returns after a shutdown signal once the
+/// in-flight requests have finished.
+pub(crate) async fn serve(app: Router) -> Result<(), Error> {
+    if let Some(path) = &CONFIG.unix_socket_path {
+        // A proxy in front of the socket terminates TLS, so the settings would be ignored
+        if CONFIG.tls_cert_path.is_some() || CONFIG.tls_key_path.is_some() {
+            return Err(
+                "tls_cert_path and tls_key_path cannot be used with unix_socket_path".into(),
+            );
+        }
+        return serve_unix_socket(app, path).await;
+    }
+
+    let address = socket_address().await?;
+    match (&CONFIG.tls_cert_path, &CONFIG.tls_key_path) {
+        (Some(cert), Some(key)) => serve_tls(app, address, cert, key).await,
+        (None, None) => {
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            tracing::info!("Listening on http://{}", listener.local_addr()?);
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+            Ok(())
+        }
+        _ => Err("tls_cert_path and tls_key_path must be set together".into()),
+    }
+}
+
+async fn socket_address() -> Result<SocketAddr, Error> {
+    let address = (CONFIG.bind_address.as_str(), CONFIG.port);
+    tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| format!("Failed to resolve the bind address {}", CONFIG.bind_address).into())
+}
+
+async fn serve_unix_socket(app: Router, path: &str) -> Result<(), Error> {
+    // A socket left behind by a previous run would make the bind fail. Anything else at the path
+    // is not ours to delete.
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(
+                format!("{path} exists and is not a socket, refusing to replace it").into(),
+            );
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    tracing::info!("Listening on the Unix socket {path}");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+async fn serve_tls(app: Router, address: SocketAddr, cert: &str, key: &str) -> Result<(), Error> {
+    // Several dependencies enable rustls, so the crypto provider has to be chosen explicitly
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let config = RustlsConfig::from_pem_file(cert, key)
+        .await
+        .map_err(|err| format!("Failed to load the TLS certificate {cert} and key {key}: {err}"))?;
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    tracing::info!("Listening on https://{address}");
+    axum_server::bind_rustls(address, config)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutting down, waiting for the in-flight requests to finish");
+    crate::routes::close_streams();
+}
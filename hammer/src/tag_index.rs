@@ -0,0 +1,333 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use fixedbitset::FixedBitSet;
+use rusqlite::Connection;
+
+use crate::db::indexed::{self, IndexEntry};
+use crate::error::Error;
+use crate::models::{FilterParams, IndexedEntity, IndexedEntityType, SourceFilter};
+
+/// The index of the server, if it was built at startup. Each test thread has its own, so the
+/// tests running in parallel do not write into the index of another.
+fn index() -> &'static OnceLock<RwLock<TagIndex>> {
+    #[cfg(not(test))]
+    {
+        static INDEX: OnceLock<RwLock<TagIndex>> = OnceLock::new();
+        &INDEX
+    }
+    #[cfg(test)]
+    {
+        thread_local! {
+            static INDEX: &'static OnceLock<RwLock<TagIndex>> = Box::leak(Box::default());
+        }
+        INDEX.with(|index| *index)
+    }
+}
+
+thread_local! {
+    /// The changes of the transaction that is open on this thread. They are applied to the
+    /// index when it commits and dropped when it rolls back.
+    static PENDING: RefCell<Vec<Change>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The state of an entity after a write, `None` if it is deleted.
+struct Change {
+    entity_type: IndexedEntityType,
+    id: i64,
+    entry: Option<IndexEntry>,
+}
+
+/// Answers the `/api/indexed` filters from memory. Each tag maps to bitsets of the ids of the
+/// entities that have it, so the filters are unions, intersections and differences of bitsets.
+#[derive(Debug, Default)]
+pub(crate) struct TagIndex {
+    abilities: EntityIndex,
+    items: EntityIndex,
+}
+
+#[derive(Debug, Default)]
+struct EntityIndex {
+    entities: HashMap<usize, IndexedEntity>,
+    verified: FixedBitSet,
+    /// The tags added by editors or through proposals.
+    human: HashMap<String, FixedBitSet>,
+    /// The tags added by imports.
+    imported: HashMap<String, FixedBitSet>,
+}
+
+/// Builds the index from the database. Until it is built, `/api/indexed` queries the
+/// database and the write paths leave the index alone.
+pub(crate) fn build(conn: &Connection) -> Result<(), Error> {
+    let index = TagIndex::load(conn)?;
+    tracing::info!(
+        "Built the tag index of {} abilities and {} items",
+        index.abilities.entities.len(),
+        index.items.entities.len()
+    );
+    self::index()
+        .set(RwLock::new(index))
+        .map_err(|_| "The tag index was already built".into())
+}
+
+/// Finds the entities matching the filter, or `None` if the index was not built.
+pub(crate) fn find(params: &FilterParams) -> Result<Option<Vec<IndexedEntity>>, Error> {
+    let Some(index) = index().get() else {
+        return Ok(None);
+    };
+    let match_all = params.match_all()?;
+    let index = index.read().unwrap_or_else(PoisonError::into_inner);
+    Ok(Some(index.find(params, match_all)))
+}
+
+/// Reloads the entity into the index once the change to it is committed. The write paths in
+/// `db::item` and `db::ability` call this after every change that can affect a filter.
+pub(crate) fn refresh(
+    entity_type: IndexedEntityType,
+    id: i64,
+    conn: &Connection,
+) -> Result<(), Error> {
+    let Some(index) = index().get() else {
+        return Ok(());
+    };
+    let entry = indexed::find_entries(entity_type, Some(id), conn)?.pop();
+    let change = Change {
+        entity_type,
+        id,
+        entry,
+    };
+    if conn.is_autocommit() {
+        // The statement was committed on its own
+        index
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .apply(change);
+    } else {
+        PENDING.with_borrow_mut(|pending| pending.push(change));
+    }
+    Ok(())
+}
+
+/// Applies the changes staged by [`refresh`] when the transaction commits and drops them when
+/// it rolls back. A thread only ever has one transaction open, so the staged changes always
+/// belong to the transaction that ends.
+pub(crate) fn install_hooks(conn: &Connection) {
+    conn.commit_hook(Some(|| {
+        let changes = PENDING.take();
+        if let Some(index) = index().get()
+            && !changes.is_empty()
+        {
+            let mut index = index.write().unwrap_or_else(PoisonError::into_inner);
+            for change in changes {
+                index.apply(change);
+            }
+        }
+        // Returning true would turn the commit into a rollback
+        false
+    }));
+    conn.rollback_hook(Some(|| PENDING.with_borrow_mut(Vec::clear)));
+}
+
+impl TagIndex {
+    fn load(conn: &Connection) -> Result<Self, Error> {
+        let mut index = Self::default();
+        for entity_type in [IndexedEntityType::Ability, IndexedEntityType::Item] {
+            for entry in indexed::find_entries(entity_type, None, conn)? {
+                index.entities_mut(entity_type).insert(entry);
+            }
+        }
+        Ok(index)
+    }
+
+    fn entities_mut(&mut self, entity_type: IndexedEntityType) -> &mut EntityIndex {
+        match entity_type {
+            IndexedEntityType::Ability => &mut self.abilities,
+            IndexedEntityType::Item => &mut self.items,
+        }
+    }
+
+    fn apply(&mut self, change: Change) {
+        let entities = self.entities_mut(change.entity_type);
+        entities.remove(change.id as usize);
+        if let Some(entry) = change.entry {
+            entities.insert(entry);
+        }
+    }
+
+    /// The abilities and then the items matching the filter, each ordered by id.
+    fn find(&self, params: &FilterParams, match_all: bool) -> Vec<IndexedEntity> {
+        let mut entities = self.abilities.find(params, match_all);
+        entities.extend(self.items.find(params, match_all));
+        entities
+    }
+}
+
+impl EntityIndex {
+    fn insert(&mut self, entry: IndexEntry) {
+        let id = entry.id as usize;
+        for (tag, source) in entry.entity.tags.iter().zip(&entry.sources) {
+            let tags = match source.as_str() {
+                "import" => &mut self.imported,
+                _ => &mut self.human,
+            };
+            let bits = tags.entry(tag.clone()).or_default();
+            bits.grow(id + 1);
+            bits.insert(id);
+        }
+        if entry.entity.verified {
+            self.verified.grow(id + 1);
+            self.verified.insert(id);
+        }
+        self.entities.insert(id, entry.entity);
+    }
+
+    fn remove(&mut self, id: usize) {
+        let Some(entity) = self.entities.remove(&id) else {
+            return;
+        };
+        for tag in &entity.tags {
+            for tags in [&mut self.human, &mut self.imported] {
+                if let Some(bits) = tags.get_mut(tag)
+                    && id < bits.len()
+                {
+                    bits.set(id, false);
+                }
+            }
+        }
+        if id < self.verified.len() {
+            self.verified.set(id, false);
+        }
+    }
+
+    /// The entities with the tag from the given source, or from any source.
+    fn with_tag(&self, tag: &str, source: Option<SourceFilter>) -> FixedBitSet {
+        let (human, imported) = match source {
+            Some(SourceFilter::Human) => (self.human.get(tag), None),
+            Some(SourceFilter::Import) => (None, self.imported.get(tag)),
+            None => (self.human.get(tag), self.imported.get(tag)),
+        };
+        let mut bits = FixedBitSet::new();
+        for tags in [human, imported].into_iter().flatten() {
+            bits.union_with(tags);
+        }
+        bits
+    }
+
+    fn find(&self, params: &FilterParams, match_all: bool) -> Vec<IndexedEntity> {
+        let mut tags = params.tags.iter();
+        let Some(first) = tags.next() else {
+            return Vec::new();
+        };
+        let mut matching = self.with_tag(first, params.source);
+        for tag in tags {
+            let bits = self.with_tag(tag, params.source);
+            if match_all {
+                matching.intersect_with(&bits);
+            } else {
+                matching.union_with(&bits);
+            }
+        }
+        for tag in &params.exclude_tags {
+            matching.difference_with(&self.with_tag(tag, None));
+        }
+        match params.verified {
+            Some(true) => matching.intersect_with(&self.verified),
+            Some(false) => matching.difference_with(&self.verified),
+            None => {}
+        }
+        matching
+            .ones()
+            .filter_map(|id| self.entities.get(&id).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, get_test_connection};
+
+    fn find_slugs(index: &TagIndex, params: &FilterParams) -> Vec<String> {
+        index
+            .find(params, params.match_all().unwrap())
+            .into_iter()
+            .map(|entity| entity.slug)
+            .collect()
+    }
+
+    #[test]
+    fn test_index_matches_the_query() {
+        let mut conn = get_test_connection();
+        indexed::insert_test_entities(&mut conn);
+        let index = TagIndex::load(&conn).unwrap();
+
+        for (params, expected) in indexed::test_filters() {
+            assert_eq!(find_slugs(&index, &params), expected, "{params:?}");
+            assert_eq!(
+                index.find(&params, params.match_all().unwrap()),
+                indexed::find(&params, &conn).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_changes_replace_the_entity() {
+        let mut conn = get_test_connection();
+        indexed::insert_test_entities(&mut conn);
+        let mut index = TagIndex::load(&conn).unwrap();
+        let params = &indexed::test_filters()[0].0;
+
+        db::item::delete("torch", &conn).unwrap();
+        let id = conn
+            .query_row("SELECT id FROM items WHERE slug = 'torch'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let entry = indexed::find_entries(IndexedEntityType::Item, Some(id), &conn)
+            .unwrap()
+            .pop();
+        assert!(entry.is_none());
+        index.apply(Change {
+            entity_type: IndexedEntityType::Item,
+            id,
+            entry,
+        });
+
+        assert_eq!(find_slugs(&index, params), vec!["frostfire"]);
+        assert_eq!(
+            index.find(params, false),
+            indexed::find(params, &conn).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_only_committed_writes_reach_the_index() {
+        let mut conn = get_test_connection();
+        indexed::insert_test_entities(&mut conn);
+        install_hooks(&conn);
+        build(&conn).unwrap();
+        let params = &indexed::test_filters()[0].0;
+        let indexed_slugs = || -> Vec<String> {
+            let entities = find(params).unwrap().unwrap();
+            entities.into_iter().map(|entity| entity.slug).collect()
+        };
+        assert_eq!(indexed_slugs(), ["frostfire", "torch"]);
+
+        // Had the rollback kept its change, the commit below would apply it too
+        let tx = conn.transaction().unwrap();
+        db::ability::delete("frostfire", &tx).unwrap();
+        drop(tx);
+        assert_eq!(indexed_slugs(), ["frostfire", "torch"]);
+
+        let tx = conn.transaction().unwrap();
+        db::item::delete("torch", &tx).unwrap();
+        assert_eq!(indexed_slugs(), ["frostfire", "torch"]);
+        tx.commit().unwrap();
+        assert_eq!(indexed_slugs(), ["frostfire"]);
+        assert_eq!(
+            find(params).unwrap().unwrap(),
+            indexed::find(params, &conn).unwrap()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use rusqlite::Connection;
+use sha2::Sha256;
+
+use crate::db::{self, Pool};
+use crate::error::Error;
+use crate::models::{ChangeEvent, PendingDelivery, WebhookPayload};
+
+/// `sha256=` and the hex HMAC-SHA256 of the body, keyed with the secret of the webhook.
+const SIGNATURE_HEADER: &str = "X-Hammer-Signature-256";
+/// The id of the delivery, which stays the same across the retries.
+const DELIVERY_HEADER: &str = "X-Hammer-Delivery";
+
+/// A delivery is given up after this many failed attempts.
+const MAX_ATTEMPTS: u32 = 8;
+/// The delay before the first retry, doubled for each further one.
+const BASE_DELAY_SECONDS: u64 = 30;
+const MAX_DELAY_SECONDS: u64 = 60 * 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the worker looks for deliveries that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: usize = 20;
+
+/// The result of one attempt to deliver a payload.
+#[derive(Debug)]
+enum Outcome {
+    Delivered(u16),
+    Failed {
+        response_status: Option<u16>,
+        error: String,
+    },
+}
+
+/// Starts the worker that delivers the changes to the webhooks. Until it is started, the
+/// deliveries queued by [`enqueue`] wait in the delivery log.
+pub(crate) fn start(pool: Pool) -> Result<(), Error> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|err| format!("Failed to create the webhook client: {err}"))?;
+    tokio::spawn(run(client, pool));
+    Ok(())
+}
+
+async fn run(client: reqwest::Client, pool: Pool) {
+    loop {
+        if let Err(err) = deliver_due(&client, &pool).await {
+            tracing::warn!("Failed to deliver the webhooks. {err:?}");
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Queues a delivery of the change to every webhook in the delivery log. Called in the
+/// transaction that makes the change, so that the deliveries are committed, or rolled back,
+/// together with it, and the worker sends them even if the server stops right after the commit.
+pub(crate) fn enqueue(event: &ChangeEvent, conn: &Connection) -> Result<usize, Error> {
+    let payload = WebhookPayload {
+        event,
+        occurred_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let payload = serde_json::to_string(&payload).map_err(|err| err.to_string())?;
+    db::webhook::enqueue(&payload, conn)
+}
+
+async fn deliver_due(client: &reqwest::Client, pool: &Pool) -> Result<(), Error> {
+    loop {
+        let due = db::run(pool, |conn| db::webhook::find_due(BATCH_SIZE, conn)).await?;
+        let more = due.len() == BATCH_SIZE;
+        for delivery in due {
+            let outcome = deliver(client, &delivery).await;
+            db::run(pool, move |conn| record(&delivery, outcome, conn)).await?;
+        }
+        if !more {
+            return Ok(());
+        }
+    }
+}
+
+async fn deliver(client: &reqwest::Client, delivery: &PendingDelivery) -> Outcome {
+    let failed = |response_status, error| Outcome::Failed {
+        response_status,
+        error,
+    };
+    let response = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&delivery.secret, &delivery.payload))
+        .header(DELIVERY_HEADER, delivery.id)
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => {
+            Outcome::Delivered(response.status().as_u16())
+        }
+        Ok(response) => failed(
+            Some(response.status().as_u16()),
+            format!("The endpoint answered {}", response.status()),
+        ),
+        Err(err) => failed(None, format!("Failed to send the request: {err}")),
+    }
+}
+
+fn record(delivery: &PendingDelivery, outcome: Outcome, conn: &Connection) -> Result<(), Error> {
+    match outcome {
+        Outcome::Delivered(status) => db::webhook::mark_delivered(delivery.id, status, conn),
+        Outcome::Failed {
+            response_status,
+            error,
+        } => {
+            let attempts = delivery.attempts + 1;
+            tracing::debug!(
+                "Attempt {attempts} of the webhook delivery {} to {} failed. {error}",
+                delivery.id,
+                delivery.url
+            );
+            let retry_in = (attempts < MAX_ATTEMPTS).then(|| backoff(attempts));
+            db::webhook::mark_attempt_failed(delivery.id, response_status, &error, retry_in, conn)
+        }
+    }
+}
+
+/// The seconds to wait after the given number of failed attempts.
+fn backoff(attempts: u32) -> u64 {
+    BASE_DELAY_SECONDS
+        .saturating_mul(1u64 << attempts.saturating_sub(1).min(32))
+        .min(MAX_DELAY_SECONDS)
+}
+
+fn sign(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{Router, extract::State, http::HeaderMap, http::StatusCode, routing::post};
+
+    use super::*;
+    use crate::db::get_test_connection;
+    use crate::models::{ChangeAction, DeliveryStatus, IndexedEntityType, NewWebhook};
+
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Answers 500 to the first request and 204 to the others, and records their signatures
+    /// and bodies.
+    async fn stand_in(
+        State(received): State<Received>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        let mut received = received.lock().unwrap();
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+        received.push((signature, body));
+        if received.len() == 1 {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::NO_CONTENT
+        }
+    }
+
+    async fn attempt_due(client: &reqwest::Client, conn: &Connection) -> usize {
+        let due = db::webhook::find_due(BATCH_SIZE, conn).unwrap();
+        for delivery in &due {
+            let outcome = deliver(client, delivery).await;
+            record(delivery, outcome, conn).unwrap();
+        }
+        due.len()
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_signed_retried_and_logged() {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(stand_in))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut conn = get_test_connection();
+        let webhook = NewWebhook {
+            url,
+            secret: "s3cret".to_string(),
+        };
+        let id = db::webhook::insert(&webhook, "admin@example.com", &conn)
+            .unwrap()
+            .id;
+        let event = ChangeEvent {
+            entity_type: IndexedEntityType::Item,
+            slug: "torch".to_string(),
+            action: ChangeAction::Updated,
+            fields: vec!["tags".to_string()],
+            author: "editor@example.com".to_string(),
+        };
+        // The deliveries of a change that is rolled back are rolled back with it
+        let tx = conn.transaction().unwrap();
+        assert_eq!(enqueue(&event, &tx).unwrap(), 1);
+        drop(tx);
+        assert!(db::webhook::find_due(BATCH_SIZE, &conn).unwrap().is_empty());
+        assert_eq!(enqueue(&event, &conn).unwrap(), 1);
+        let client = reqwest::Client::new();
+
+        assert_eq!(attempt_due(&client, &conn).await, 1);
+        let log = db::webhook::find_deliveries(id, 10, &conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(log[0].status, DeliveryStatus::Pending);
+        assert_eq!(log[0].response_status, Some(500));
+        // The retry is not due yet
+        assert_eq!(attempt_due(&client, &conn).await, 0);
+
+        conn.execute(
+            "UPDATE webhook_deliveries SET next_attempt_at = CURRENT_TIMESTAMP",
+            [],
+        )
+        .unwrap();
+        assert_eq!(attempt_due(&client, &conn).await, 1);
+        let log = db::webhook::find_deliveries(id, 10, &conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(log[0].status, DeliveryStatus::Delivered);
+        assert_eq!(log[0].attempts, 2);
+
+        let received = received.lock().unwrap();
+        let (signature, body) = &received[1];
+        assert_eq!(*signature, sign("s3cret", body));
+        assert_ne!(*signature, sign("other", body));
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["slug"], "torch");
+        assert_eq!(payload["fields"][0], "tags");
+
+        assert_eq!(backoff(1), BASE_DELAY_SECONDS);
+        assert_eq!(backoff(3), 4 * BASE_DELAY_SECONDS);
+        assert_eq!(backoff(MAX_ATTEMPTS + 20), MAX_DELAY_SECONDS);
+    }
+}